@@ -0,0 +1,745 @@
+//! Command line argument parsing.
+
+use std::ffi::OsString;
+use std::io::Read;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use url::Url;
+
+use crate::scrapers::browser::BrowserOptions;
+use crate::scrapers::crawl::{CrawlConfig, PolitenessProfile};
+use crate::scrapers::manifest::FailCondition;
+use crate::scrapers::registry;
+
+/// Help text printed when the command line can't be parsed.
+pub const USAGE: &str = "\
+Usage: proconfort-rust-scraper [scrape] [options]
+       proconfort-rust-scraper replay <trace-file> [browser options]
+       proconfort-rust-scraper console <file-or-url> [browser options]
+       proconfort-rust-scraper scrapers list
+       proconfort-rust-scraper stats [--site <id>]...
+       proconfort-rust-scraper verify [--site <id>]...
+
+Commands:
+    scrape    Crawl product listings and save their page sources (default).
+    replay    Issue the WebDriver commands recorded with --trace again.
+    console   Open a saved page source or a URL and try out CSS selectors on it.
+    scrapers  List the available scrapers, with `scrapers list`.
+    stats     Print statistics over the saved page sources, to check crawls are complete.
+    verify    Check the saved page sources against the checksums in run.json.
+
+Scrape options:
+    --site <id>                Site to scrape. Can be repeated. Defaults to all known sites.
+    --max-pages <n>            Stop crawling a listing after saving this many pages.
+    --max-products <n>         Stop crawling a listing once this many products were found.
+    --max-duration <duration>  Stop crawling a listing after this long, e.g. 90s or 10m.
+    --include <glob>           Only follow URLs matching this pattern. Can be repeated.
+    --exclude <glob>           Never follow URLs matching this pattern. Can be repeated.
+    --resume                   Resume an interrupted crawl, reusing the pages it already saved.
+    --product-list-limit <n>   Ask for this many products per listing page, or `all`. Defaults to
+                               the site's maximum.
+    --keep-page-size           Crawl listing URLs as they are, if a site refuses --product-list-limit.
+    --parallel                 Crawl a site's listings at the same time, each in its own browser.
+    --new-arrivals             Crawl listings newest first, stopping at products seen by earlier runs.
+    --urls <file>              Save the pages at the URLs in this file, one per line, instead of
+                               crawling listings. Reads them from stdin if the file is `-`.
+                               Keeps the previous run.json and report.html.
+    --politeness <profile>     Limit the load on a host, e.g. www.climatico.ro,delay=2s,sessions=2.
+                               Settings: delay, sessions, user-agent and proxy (host:port).
+                               Can be repeated, once per host.
+    --wait-for <selector>      Wait for an element matching this CSS selector before saving a page.
+    --wait-for-network-idle    Wait for a page to stop loading resources before saving it.
+    --wait-timeout <duration>  Longest time to wait for a page to render. Defaults to 10s.
+    --cache-ttl <duration>     Reuse pages fetched by earlier runs for this long, e.g. 12h.
+    --refresh                  Fetch every page again, ignoring pages cached with --cache-ttl.
+    --discover                 Save links to other pages of the site in out/<site>/discovered_urls.tsv.
+    --prune                    After crawling whole listings, delete the saved pages the crawl didn't
+                               save again and the pages expired from the cache.
+    --strict                   Exit with an error if a crawl is degraded, e.g. pages had no products.
+    --fail-on <condition>      Exit with an error if a run meets this condition. Can be repeated.
+                               Conditions: product-drop:<percent> and page-drop:<percent>, compared
+                               with the previous run, degraded and blocked.
+    --trace                    Record browser commands in out/<site>/webdriver_trace.jsonl.
+
+Browser options:
+    --headed                   Show the browser window instead of running headless.
+    --slowmo <duration>        Pause before every browser command, e.g. 250ms. Not for console.
+    --devtools                 Open the browser's developer tools. Implies --headed.
+    --accept-language <langs>  Languages to ask sites for, e.g. ro-RO,ro.
+
+Environment:
+    SCRAPER__<COMMAND>__<OPTION>=<value>
+                               Sets an option of a command, e.g. SCRAPER__SCRAPE__MAX_PAGES=5 for
+                               --max-pages 5. Options without a value are set with `true`. Options
+                               given on the command line replace those set this way, including
+                               repeatable ones like --site.
+
+Exit codes:
+    0    Success.
+    1    Failure, e.g. the browser or a file couldn't be used.
+    2    Invalid arguments.
+    3    A crawl was degraded, with --strict.
+    4    A run met a --fail-on condition.
+    5    verify found missing or changed files.
+    6    A site showed prices in another currency than expected, e.g. through a VPN.";
+
+/// A command given on the command line.
+#[derive(Debug)]
+pub enum Command {
+    /// Crawl the product listings of one or more sites.
+    Scrape(ScrapeArgs),
+    /// Replay a trace of WebDriver commands.
+    Replay(ReplayArgs),
+    /// Try out CSS selectors interactively.
+    Console(ConsoleArgs),
+    /// List the available scrapers.
+    ListScrapers,
+    /// Print statistics over the saved page sources of one or more sites.
+    Stats(StatsArgs),
+    /// Check the saved page sources of one or more sites against their checksums.
+    Verify(StatsArgs),
+}
+
+/// Arguments for the `scrape` command.
+#[derive(Debug)]
+pub struct ScrapeArgs {
+    /// Ids of the sites to scrape, in the order they were given.
+    pub site_ids: Vec<String>,
+    /// Crawl configuration built from the options.
+    pub crawl_config: CrawlConfig,
+    /// How to start the browser.
+    pub browser_options: BrowserOptions,
+    /// Conditions that fail the program when a site's run meets them.
+    pub fail_on: Vec<FailCondition>,
+}
+
+/// Arguments for the `replay` command.
+#[derive(Debug)]
+pub struct ReplayArgs {
+    /// Path of the trace file to replay.
+    pub trace_path: PathBuf,
+    /// How to start the browser.
+    pub browser_options: BrowserOptions,
+}
+
+/// Arguments for the `console` command.
+#[derive(Debug)]
+pub struct ConsoleArgs {
+    /// Path of a saved page source, or URL of the page to open.
+    pub target: String,
+    /// How to start the browser.
+    pub browser_options: BrowserOptions,
+}
+
+/// Arguments for the `stats` and `verify` commands.
+#[derive(Debug)]
+pub struct StatsArgs {
+    /// Ids of the sites to check, in the order they were given.
+    pub site_ids: Vec<String>,
+}
+
+/// Prefix of the environment variables setting command options, see [with_env_overrides].
+pub const ENV_PREFIX: &str = "SCRAPER__";
+
+/// Commands that take no options, so environment variables can't set any, e.g. `scrapers list`.
+const COMMANDS_WITHOUT_OPTIONS: &[&str] = &["scrapers"];
+
+/// Adds the options set by environment variables in `vars` to the command line arguments
+/// `args`, excluding the program name.
+///
+/// A variable named `SCRAPER__<COMMAND>__<OPTION>` sets an option of a command, e.g.
+/// `SCRAPER__SCRAPE__MAX_PAGES=5` becomes `--max-pages 5`. A value of `true` gives the option
+/// without a value, e.g. `--parallel`, and `false` or an empty value leaves it out.
+///
+/// Options given on the command line take precedence: a variable for an option that is also on
+/// the command line is ignored, even for options that can be repeated, like `--site`. Variables
+/// whose name or value isn't valid Unicode are ignored too, as are variables for
+/// [commands without options](COMMANDS_WITHOUT_OPTIONS).
+pub fn with_env_overrides<I, V>(args: I, vars: V) -> Vec<String>
+where
+    I: IntoIterator<Item = String>,
+    V: IntoIterator<Item = (OsString, OsString)>,
+{
+    let mut args: Vec<String> = args.into_iter().collect();
+    let (command, position) = match args.first() {
+        Some(a) if COMMANDS_WITHOUT_OPTIONS.contains(&a.as_str()) => return args,
+        Some(a) if !a.starts_with("--") => (a.to_uppercase(), 1),
+        _ => ("SCRAPE".to_string(), 0),
+    };
+    let section_prefix = format!("{}{}__", ENV_PREFIX, command);
+    let mut overrides: Vec<(String, String)> = vars
+        .into_iter()
+        .filter_map(|(name, value)| {
+            let name = name.into_string().ok()?;
+
+            if !name.starts_with(&section_prefix) {
+                return None;
+            }
+
+            let option = format!(
+                "--{}",
+                name[section_prefix.len()..]
+                    .to_lowercase()
+                    .replace('_', "-")
+            );
+
+            Some((option, value.into_string().ok()?))
+        })
+        .filter(|(option, _)| !args.contains(option))
+        .collect();
+    // Environment variables come in no particular order.
+    overrides.sort();
+
+    let mut env_args = Vec::new();
+
+    for (option, value) in overrides {
+        match value.as_str() {
+            "true" => env_args.push(option),
+            "false" | "" => {}
+            _ => env_args.extend(vec![option, value]),
+        }
+    }
+
+    args.splice(position..position, env_args);
+    args
+}
+
+/// Parses the command line arguments, excluding the program name.
+///
+/// Running without any arguments is the same as `scrape` for all known sites.
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Command, String> {
+    let mut args = args.into_iter().peekable();
+
+    match args.peek().map(String::as_str) {
+        Some("scrape") => {
+            args.next();
+        }
+        Some("replay") => {
+            args.next();
+
+            return parse_replay_args(args);
+        }
+        Some("console") => {
+            args.next();
+
+            return parse_console_args(args);
+        }
+        Some("stats") => {
+            args.next();
+
+            return parse_stats_args(args).map(Command::Stats);
+        }
+        Some("verify") => {
+            args.next();
+
+            return parse_stats_args(args).map(Command::Verify);
+        }
+        Some("scrapers") => {
+            args.next();
+
+            return match (args.next().as_deref(), args.next()) {
+                (Some("list"), None) => Ok(Command::ListScrapers),
+                (Some("list"), Some(arg)) => Err(format!("Unknown argument: {}", arg)),
+                (Some(a), _) => Err(format!("Unknown scrapers command: {}", a)),
+                (None, _) => Err("Missing scrapers command, e.g. list.".to_string()),
+            };
+        }
+        Some(a) if !a.starts_with("--") => {
+            return Err(format!("Unknown command: {}", a));
+        }
+        _ => {}
+    }
+
+    parse_scrape_args(args)
+}
+
+/// Parses the arguments of the `scrape` command.
+fn parse_scrape_args<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut site_ids = Vec::new();
+    let mut crawl_config = CrawlConfig::default();
+    let mut browser_options = BrowserOptions::default();
+    let mut fail_on = Vec::new();
+
+    while let Some(arg) = args.next() {
+        if parse_browser_option(&arg, &mut args, &mut browser_options)? {
+            continue;
+        }
+
+        match arg.as_str() {
+            "--site" => push_site_id(&mut site_ids, option_value(&mut args, &arg)?)?,
+            "--max-pages" => {
+                let value = option_value(&mut args, &arg)?;
+                let max_pages = value
+                    .parse()
+                    .map_err(|_| format!("Invalid value for {}: {}", arg, value))?;
+
+                crawl_config.limits.max_pages = Some(max_pages);
+            }
+            "--max-products" => {
+                let value = option_value(&mut args, &arg)?;
+                let max_products = value
+                    .parse()
+                    .map_err(|_| format!("Invalid value for {}: {}", arg, value))?;
+
+                crawl_config.limits.max_products = Some(max_products);
+            }
+            "--max-duration" => {
+                let value = option_value(&mut args, &arg)?;
+
+                crawl_config.limits.max_duration = Some(parse_duration(&value)?);
+            }
+            "--include" => {
+                let pattern = option_value(&mut args, &arg)?;
+
+                crawl_config.url_filter.include.push(pattern);
+            }
+            "--exclude" => {
+                let pattern = option_value(&mut args, &arg)?;
+
+                crawl_config.url_filter.exclude.push(pattern);
+            }
+            "--resume" => crawl_config.resume = true,
+            "--product-list-limit" => {
+                let value = option_value(&mut args, &arg)?;
+
+                if value != "all" && value.parse::<u32>().is_err() {
+                    return Err(format!("Invalid value for {}: {}", arg, value));
+                }
+
+                crawl_config.product_list_limit = Some(value);
+            }
+            "--keep-page-size" => crawl_config.keep_page_size = true,
+            "--parallel" => crawl_config.parallel = true,
+            "--new-arrivals" => crawl_config.new_arrivals = true,
+            "--urls" => {
+                let path = option_value(&mut args, &arg)?;
+
+                crawl_config.urls.extend(read_url_list(&path)?);
+            }
+            "--politeness" => {
+                let value = option_value(&mut args, &arg)?;
+                let (host, profile) = parse_politeness_profile(&value)?;
+
+                crawl_config.politeness.insert(host, profile);
+            }
+            "--wait-for" => {
+                let selector = option_value(&mut args, &arg)?;
+
+                crawl_config.render_wait.selector = Some(selector);
+            }
+            "--wait-for-network-idle" => crawl_config.render_wait.network_idle = true,
+            "--wait-timeout" => {
+                let value = option_value(&mut args, &arg)?;
+
+                crawl_config.render_wait.timeout = parse_duration(&value)?;
+            }
+            "--cache-ttl" => {
+                let value = option_value(&mut args, &arg)?;
+
+                crawl_config.cache_ttl = Some(parse_duration(&value)?);
+            }
+            "--refresh" => crawl_config.refresh = true,
+            "--discover" => crawl_config.discover = true,
+            "--prune" => crawl_config.prune = true,
+            "--strict" => crawl_config.strict = true,
+            "--fail-on" => {
+                let value = option_value(&mut args, &arg)?;
+
+                fail_on.push(value.parse()?);
+            }
+            "--trace" => browser_options.trace_commands = true,
+            _ => return Err(format!("Unknown argument: {}", arg)),
+        }
+    }
+
+    if site_ids.is_empty() {
+        site_ids = all_site_ids();
+    }
+
+    let mut problems = crawl_config.validate();
+    let registry = registry();
+    let site_hosts: Vec<Option<String>> = site_ids
+        .iter()
+        .filter_map(|id| registry.get(id))
+        .map(|s| Url::parse(s.base_url).ok()?.host_str().map(String::from))
+        .collect();
+
+    for (i, url) in crawl_config.urls.iter().enumerate() {
+        if let Ok(url) = Url::parse(url) {
+            if !site_hosts.contains(&url.host_str().map(String::from)) {
+                problems.push(format!(
+                    "urls[{}]: No scraped site is on the host of {}.",
+                    i, url
+                ));
+            }
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(format!(
+            "Invalid configuration:\n  {}",
+            problems.join("\n  ")
+        ));
+    }
+
+    Ok(Command::Scrape(ScrapeArgs {
+        site_ids,
+        crawl_config,
+        browser_options,
+        fail_on,
+    }))
+}
+
+/// Parses the arguments of the `stats` and `verify` commands.
+fn parse_stats_args<I: Iterator<Item = String>>(mut args: I) -> Result<StatsArgs, String> {
+    let mut site_ids = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--site" => push_site_id(&mut site_ids, option_value(&mut args, &arg)?)?,
+            _ => return Err(format!("Unknown argument: {}", arg)),
+        }
+    }
+
+    if site_ids.is_empty() {
+        site_ids = all_site_ids();
+    }
+
+    Ok(StatsArgs { site_ids })
+}
+
+/// Parses the arguments of the `replay` command.
+fn parse_replay_args<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut trace_path = None;
+    let mut browser_options = BrowserOptions::default();
+
+    while let Some(arg) = args.next() {
+        if parse_browser_option(&arg, &mut args, &mut browser_options)? {
+            continue;
+        }
+
+        if arg.starts_with("--") || trace_path.is_some() {
+            return Err(format!("Unknown argument: {}", arg));
+        }
+
+        trace_path = Some(PathBuf::from(arg));
+    }
+
+    Ok(Command::Replay(ReplayArgs {
+        trace_path: trace_path.ok_or_else(|| "Missing trace file to replay.".to_string())?,
+        browser_options,
+    }))
+}
+
+/// Parses the arguments of the `console` command.
+fn parse_console_args<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut target = None;
+    let mut browser_options = BrowserOptions::default();
+
+    while let Some(arg) = args.next() {
+        // The console issues its commands as they're typed in.
+        if arg == "--slowmo" {
+            return Err("--slowmo can't be used with console.".to_string());
+        }
+
+        if parse_browser_option(&arg, &mut args, &mut browser_options)? {
+            continue;
+        }
+
+        if arg.starts_with("--") || target.is_some() {
+            return Err(format!("Unknown argument: {}", arg));
+        }
+
+        target = Some(arg);
+    }
+
+    Ok(Command::Console(ConsoleArgs {
+        target: target.ok_or_else(|| "Missing file or URL to open.".to_string())?,
+        browser_options,
+    }))
+}
+
+/// Parses `arg` into `browser_options` if it's one of the options for starting the browser,
+/// taking its value from `args`.
+///
+/// Returns `false` if `arg` is not a browser option.
+fn parse_browser_option<I: Iterator<Item = String>>(
+    arg: &str,
+    args: &mut I,
+    browser_options: &mut BrowserOptions,
+) -> Result<bool, String> {
+    match arg {
+        "--headed" => browser_options.headed = true,
+        "--slowmo" => {
+            let value = option_value(args, arg)?;
+
+            browser_options.slowmo = Some(parse_duration(&value)?);
+        }
+        "--devtools" => browser_options.devtools = true,
+        "--accept-language" => {
+            browser_options.accept_language = Some(option_value(args, arg)?);
+        }
+        _ => return Ok(false),
+    }
+
+    Ok(true)
+}
+
+/// Adds `site_id` to `site_ids`, unless it's already there.
+///
+/// Fails if there is no scraper for the site.
+fn push_site_id(site_ids: &mut Vec<String>, site_id: String) -> Result<(), String> {
+    let known_site_ids = registry().site_ids();
+
+    if !known_site_ids.contains(&site_id.as_str()) {
+        return Err(format!(
+            "Unknown site: {}. Known sites: {}.",
+            site_id,
+            known_site_ids.join(", ")
+        ));
+    }
+
+    if !site_ids.contains(&site_id) {
+        site_ids.push(site_id);
+    }
+
+    Ok(())
+}
+
+/// Ids of all the sites that have a scraper.
+fn all_site_ids() -> Vec<String> {
+    registry()
+        .site_ids()
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+
+/// Reads the URLs in the file at `path`, or in stdin if `path` is `-`, one per line. Blank
+/// lines and lines starting with `#` are skipped.
+fn read_url_list(path: &str) -> Result<Vec<String>, String> {
+    let text = if path == "-" {
+        let mut text = String::new();
+
+        std::io::stdin()
+            .read_to_string(&mut text)
+            .map_err(|e| format!("Failed to read URLs from stdin: {}", e))?;
+
+        text
+    } else {
+        std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read URLs from {}: {}", path, e))?
+    };
+
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
+
+/// Takes the value following the option named `option`.
+fn option_value<I: Iterator<Item = String>>(args: &mut I, option: &str) -> Result<String, String> {
+    args.next()
+        .ok_or_else(|| format!("Missing value for {}.", option))
+}
+
+/// Parses a politeness profile written as a host followed by comma separated settings, e.g.
+/// `www.climatico.ro,delay=2s,sessions=2,user-agent=Mozilla/5.0,proxy=localhost:3128`.
+pub fn parse_politeness_profile(value: &str) -> Result<(String, PolitenessProfile), String> {
+    let mut parts = value.split(',');
+    // `split` always yields at least one part.
+    let host = parts.next().unwrap().trim();
+
+    if host.is_empty() || host.contains('=') {
+        return Err(format!(
+            "Invalid politeness profile: {}. Expected a host first.",
+            value
+        ));
+    }
+
+    let mut profile = PolitenessProfile::default();
+
+    for setting in parts {
+        let (key, setting_value) = match setting.find('=') {
+            Some(i) => (&setting[..i], &setting[i + 1..]),
+            None => return Err(format!("Invalid politeness setting: {}", setting)),
+        };
+
+        match key {
+            "delay" => profile.delay = Some(parse_duration(setting_value)?),
+            "sessions" => {
+                let sessions = setting_value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or_else(|| format!("Invalid number of sessions: {}", setting_value))?;
+
+                profile.max_sessions = Some(sessions);
+            }
+            "user-agent" => profile.user_agent = Some(setting_value.to_string()),
+            "proxy" => profile.proxy = Some(setting_value.to_string()),
+            _ => {
+                return Err(format!(
+                    "Unknown politeness setting: {}. Expected delay, sessions, user-agent or \
+                     proxy.",
+                    key
+                ))
+            }
+        }
+    }
+
+    Ok((host.to_string(), profile))
+}
+
+/// Parses a duration made of a whole number and a unit: `ms`, `s`, `m` or `h`.
+///
+/// # Examples
+///
+/// * `250ms`,
+/// * `90s`,
+/// * `10m`.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let unit_start = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(unit_start);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("Invalid duration: {}", value))?;
+
+    match unit {
+        "ms" => Ok(Duration::from_millis(number)),
+        "s" => Ok(Duration::from_secs(number)),
+        "m" => Ok(Duration::from_secs(number * 60)),
+        "h" => Ok(Duration::from_secs(number * 60 * 60)),
+        _ => Err(format!(
+            "Invalid duration: {}. Expected a unit of ms, s, m or h.",
+            value
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(OsString, OsString)> {
+        vars.iter()
+            .map(|(k, v)| (OsString::from(k), OsString::from(v)))
+            .collect()
+    }
+
+    #[test]
+    fn parses_durations_with_units() {
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("10m"), Ok(Duration::from_secs(600)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+    }
+
+    #[test]
+    fn rejects_durations_without_a_valid_unit_or_number() {
+        assert!(parse_duration("90").is_err());
+        assert!(parse_duration("90d").is_err());
+        assert!(parse_duration("s").is_err());
+        assert!(parse_duration("1.5s").is_err());
+    }
+
+    #[test]
+    fn env_overrides_follow_the_command() {
+        let overridden = with_env_overrides(
+            args(&["scrape", "--resume"]),
+            vars(&[
+                ("SCRAPER__SCRAPE__MAX_PAGES", "5"),
+                ("SCRAPER__SCRAPE__PARALLEL", "true"),
+                ("SCRAPER__SCRAPE__DISCOVER", "false"),
+                ("SCRAPER__STATS__SITE", "climatico"),
+                ("PATH", "/usr/bin"),
+            ]),
+        );
+
+        assert_eq!(
+            overridden,
+            args(&["scrape", "--max-pages", "5", "--parallel", "--resume"])
+        );
+    }
+
+    #[test]
+    fn env_overrides_default_to_the_scrape_command() {
+        let overridden = with_env_overrides(
+            args(&["--resume"]),
+            vars(&[("SCRAPER__SCRAPE__MAX_PAGES", "5")]),
+        );
+
+        assert_eq!(overridden, args(&["--max-pages", "5", "--resume"]));
+    }
+
+    #[test]
+    fn command_line_options_replace_env_overrides() {
+        let overridden = with_env_overrides(
+            args(&["scrape", "--site", "climatico"]),
+            vars(&[
+                ("SCRAPER__SCRAPE__SITE", "other"),
+                ("SCRAPER__SCRAPE__MAX_PAGES", "5"),
+            ]),
+        );
+
+        assert_eq!(
+            overridden,
+            args(&["scrape", "--max-pages", "5", "--site", "climatico"])
+        );
+    }
+
+    #[test]
+    fn env_overrides_skip_commands_without_options() {
+        let overridden = with_env_overrides(
+            args(&["scrapers", "list"]),
+            vars(&[("SCRAPER__SCRAPERS__VERBOSE", "true")]),
+        );
+
+        assert_eq!(overridden, args(&["scrapers", "list"]));
+        match parse_args(overridden) {
+            Ok(Command::ListScrapers) => {}
+            other => panic!("Parsed as {:?}", other),
+        }
+    }
+
+    #[test]
+    fn slowmo_is_only_for_commands_that_use_it() {
+        match parse_args(args(&["replay", "trace.jsonl", "--slowmo", "250ms"])) {
+            Ok(Command::Replay(replay)) => assert_eq!(
+                replay.browser_options.slowmo,
+                Some(Duration::from_millis(250))
+            ),
+            other => panic!("Parsed as {:?}", other),
+        }
+
+        assert!(parse_args(args(&["console", "page.html", "--slowmo", "250ms"])).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn env_overrides_skip_non_unicode_variables() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let overridden = with_env_overrides(
+            args(&["scrape"]),
+            vec![
+                (
+                    OsString::from("SCRAPER__SCRAPE__MAX_PAGES"),
+                    OsString::from_vec(vec![0xff]),
+                ),
+                (OsString::from_vec(vec![0xff]), OsString::from("5")),
+            ],
+        );
+
+        assert_eq!(overridden, args(&["scrape"]));
+    }
+}
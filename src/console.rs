@@ -0,0 +1,154 @@
+//! An interactive console for trying out CSS selectors against a page.
+//!
+//! The page, a saved page source or a live URL, is opened in the browser and every line typed
+//! in is evaluated as a CSS selector, printing the matching elements. Lines starting with `:`
+//! are console commands, see [HELP].
+
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use fantoccini::error::CmdError;
+use fantoccini::{Client, Locator};
+use url::Url;
+
+/// Help text printed by the `:help` command.
+pub const HELP: &str = "\
+Type a CSS selector to print the elements matching it, or one of these commands:
+    :text <selector>         Print the text of the elements matching the selector.
+    :attr <name> <selector>  Print an attribute of the elements matching the selector.
+    :open <file-or-url>      Open another page.
+    :reload                  Open the current page again, e.g. after saving it again.
+    :help                    Print this help.
+    :quit                    Leave the console.";
+
+/// Longest preview of an element's HTML, in characters.
+const PREVIEW_LENGTH: usize = 200;
+
+/// Returns the URL to open for `target`, which is either a URL or the path of a saved page
+/// source.
+pub fn target_url(target: &str) -> Result<Url, String> {
+    match Url::parse(target) {
+        Ok(url) if ["http", "https", "file"].contains(&url.scheme()) => Ok(url),
+        _ => {
+            let path = Path::new(target)
+                .canonicalize()
+                .map_err(|e| format!("Failed to open {}: {}", target, e))?;
+
+            Url::from_file_path(&path).map_err(|_| format!("Failed to open {}.", target))
+        }
+    }
+}
+
+/// Collapses whitespace in `html` and shortens it to [PREVIEW_LENGTH] characters.
+fn preview(html: &str) -> String {
+    let html = html.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    match html.char_indices().nth(PREVIEW_LENGTH) {
+        Some((i, _)) => format!("{}...", &html[..i]),
+        None => html,
+    }
+}
+
+/// What to print for each element matching a selector.
+enum Show<'a> {
+    Html,
+    Text,
+    Attr(&'a str),
+}
+
+/// Prints what `show` asks for of each element matching `selector` on the current page.
+async fn print_matches(
+    client: &mut Client,
+    selector: &str,
+    show: Show<'_>,
+) -> Result<(), CmdError> {
+    let mut elements = client.find_all(Locator::Css(selector)).await?;
+
+    println!("{} matching element(s).", elements.len());
+
+    for (i, element) in elements.iter_mut().enumerate() {
+        let value = match show {
+            Show::Html => preview(&element.html(false).await?),
+            Show::Text => element.text().await?,
+            Show::Attr(name) => format!("{:?}", element.attr(name).await?),
+        };
+
+        println!("[{}] {}", i + 1, value);
+    }
+
+    Ok(())
+}
+
+/// Opens `url` with `client` and evaluates the lines read from standard input against it,
+/// until `:quit` or the end of input.
+pub async fn run(client: &mut Client, mut url: Url) -> Result<(), CmdError> {
+    client.goto(url.as_str()).await?;
+
+    println!("Opened {}. Type :help for help.", url);
+
+    let stdin = std::io::stdin();
+
+    loop {
+        print!("> ");
+        std::io::stdout()
+            .flush()
+            .expect("Failed to flush standard output.");
+
+        let mut line = String::new();
+
+        if stdin
+            .lock()
+            .read_line(&mut line)
+            .expect("Failed to read standard input.")
+            == 0
+        {
+            break;
+        }
+
+        let line = line.trim();
+        let (command, rest) = match line.find(char::is_whitespace) {
+            Some(i) => (&line[..i], line[i..].trim()),
+            None => (line, ""),
+        };
+
+        let result = match command {
+            "" => Ok(()),
+            ":quit" | ":q" => break,
+            ":help" => {
+                println!("{}", HELP);
+                Ok(())
+            }
+            ":reload" => client.goto(url.as_str()).await,
+            ":open" => match target_url(rest) {
+                Ok(u) => {
+                    url = u;
+                    client.goto(url.as_str()).await
+                }
+                Err(e) => {
+                    println!("{}", e);
+                    Ok(())
+                }
+            },
+            ":text" => print_matches(client, rest, Show::Text).await,
+            ":attr" => match rest.find(char::is_whitespace) {
+                Some(i) => print_matches(client, rest[i..].trim(), Show::Attr(&rest[..i])).await,
+                None => {
+                    println!("Usage: :attr <name> <selector>");
+                    Ok(())
+                }
+            },
+            c if c.starts_with(':') => {
+                println!("Unknown command: {}. Type :help for help.", c);
+                Ok(())
+            }
+            _ => print_matches(client, line, Show::Html).await,
+        };
+
+        // Invalid selectors and the like shouldn't end the session.
+        if let Err(e) = result {
+            println!("Error: {}", e);
+        }
+    }
+
+    Ok(())
+}
@@ -1,9 +1,30 @@
-use log::info;
+use std::path::Path;
 
-use crate::scrapers::climatico::ClimaticoScraper;
+use log::{error, info, warn};
 
+use crate::cli::Command;
+use crate::scrapers::manifest::RunManifest;
+use crate::scrapers::{browser, html_report, stats, trace, SiteOutputPaths};
+
+pub mod cli;
+pub mod console;
 pub mod scrapers;
 
+/// Folder under which every site's output is namespaced by its id.
+const OUTPUT_ROOT: &str = "./out";
+
+/// Exit code when a crawl is degraded in strict mode.
+const EXIT_DEGRADED: i32 = 3;
+
+/// Exit code when a run meets a `--fail-on` condition.
+const EXIT_ANOMALY: i32 = 4;
+
+/// Exit code when `verify` finds missing or changed files.
+const EXIT_VERIFY_FAILED: i32 = 5;
+
+/// Exit code when a site shows prices in the wrong currency.
+const EXIT_WRONG_CURRENCY: i32 = 6;
+
 /// Initialize application state before startup.
 fn init() {
     env_logger::init();
@@ -14,12 +35,175 @@ fn init() {
 async fn main() -> Result<(), fantoccini::error::CmdError> {
     init();
 
-    let mut climatico_scraper =
-        ClimaticoScraper::new("./out/climatico/sources/", "./out/climatico/product_info/");
+    let args = cli::with_env_overrides(std::env::args().skip(1), std::env::vars_os());
+    let command = match cli::parse_args(args) {
+        Ok(c) => c,
+        Err(e) => {
+            error!("{}", e);
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    match command {
+        Command::Scrape(args) => {
+            // Checked before crawling, so a run doesn't fail after its first site.
+            if let Err(e) = std::fs::create_dir_all(OUTPUT_ROOT) {
+                error!("Can't create the output folder {}: {}", OUTPUT_ROOT, e);
+                std::process::exit(1);
+            }
+
+            let mut degraded_site_ids = Vec::new();
+            let mut anomalies = Vec::new();
+
+            for site_id in &args.site_ids {
+                let output_paths = SiteOutputPaths::new(Path::new(OUTPUT_ROOT), site_id);
+                // Read before scraping, which removes it.
+                let previous_manifest = RunManifest::read(&output_paths.run_manifest).ok();
+
+                let manifest = scrapers::scrape_site(
+                    site_id,
+                    Path::new(OUTPUT_ROOT),
+                    &args.crawl_config,
+                    &args.browser_options,
+                )
+                .await?;
+
+                // Saving only the given URLs isn't a run of the whole site, to report on or to
+                // compare with the previous run.
+                let saves_given_urls = !args.crawl_config.urls.is_empty();
+                let previous_manifest = previous_manifest.filter(|_| !saves_given_urls);
+
+                if !saves_given_urls {
+                    if let Err(e) = html_report::write(
+                        &output_paths.run_report,
+                        &manifest,
+                        previous_manifest.as_ref(),
+                    ) {
+                        warn!("Failed to write the run report of site {}: {}", site_id, e);
+                    }
+                }
+
+                // Other sites are likely served through the same VPN or proxy, so there's no
+                // point going on.
+                if let Some(reason) = &manifest.report.wrong_currency {
+                    error!("Aborting, site {}: {}", site_id, reason);
+                    std::process::exit(EXIT_WRONG_CURRENCY);
+                }
+
+                if manifest.report.degraded {
+                    degraded_site_ids.push(site_id.as_str());
+                }
+
+                for condition in &args.fail_on {
+                    if let Some(anomaly) = manifest.check(condition, previous_manifest.as_ref()) {
+                        anomalies.push(format!("{}: {}", site_id, anomaly));
+                    }
+                }
+            }
+
+            if args.crawl_config.strict && !degraded_site_ids.is_empty() {
+                error!(
+                    "Degraded crawls in strict mode: {}.",
+                    degraded_site_ids.join(", ")
+                );
+                std::process::exit(EXIT_DEGRADED);
+            }
+
+            if !anomalies.is_empty() {
+                for anomaly in &anomalies {
+                    error!("{}", anomaly);
+                }
+                std::process::exit(EXIT_ANOMALY);
+            }
+        }
+        Command::Replay(args) => {
+            let entries = match trace::read_trace(&args.trace_path) {
+                Ok(e) => e,
+                Err(e) => {
+                    error!("Failed to read trace file {:?}: {}", args.trace_path, e);
+                    std::process::exit(1);
+                }
+            };
+            let mut client = browser::connect(&args.browser_options);
+
+            trace::replay(&mut client, &entries, args.browser_options.slowmo).await?;
+        }
+        Command::Console(args) => {
+            let url = match console::target_url(&args.target) {
+                Ok(u) => u,
+                Err(e) => {
+                    error!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            let mut client = browser::connect(&args.browser_options);
+
+            console::run(&mut client, url).await?;
+        }
+        Command::Stats(args) => {
+            let registry = scrapers::registry();
+
+            for site_id in &args.site_ids {
+                let scraper_info = registry
+                    .get(site_id)
+                    .expect("Site ids are validated when parsing arguments.");
+                let output_paths = SiteOutputPaths::new(Path::new(OUTPUT_ROOT), site_id);
+
+                match stats::site_stats(scraper_info, &output_paths) {
+                    Ok(s) => print!("{}", s),
+                    Err(e) => {
+                        error!("Failed to read the output of site {}: {}", site_id, e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+        Command::Verify(args) => {
+            let mut problem_count = 0;
+
+            for site_id in &args.site_ids {
+                let output_paths = SiteOutputPaths::new(Path::new(OUTPUT_ROOT), site_id);
+                let manifest = match RunManifest::read(&output_paths.run_manifest) {
+                    Ok(m) => m,
+                    Err(e) => {
+                        error!("Failed to read the run manifest of site {}: {}", site_id, e);
+                        std::process::exit(1);
+                    }
+                };
+                let problems = manifest.verify_checksums(&output_paths.page_sources);
+
+                if manifest.checksums.is_empty() {
+                    warn!("The last run of site {} has no checksums.", site_id);
+                }
+
+                for problem in &problems {
+                    error!("{}: {}", site_id, problem);
+                }
+
+                info!(
+                    "Verified {} files of site {}, {} with problems.",
+                    manifest.checksums.len(),
+                    site_id,
+                    problems.len()
+                );
+
+                problem_count += problems.len();
+            }
 
-    climatico_scraper
-        .save_page_sources("https://www.climatico.ro/aer-conditionat/comercial")
-        .await?;
+            if problem_count > 0 {
+                std::process::exit(EXIT_VERIFY_FAILED);
+            }
+        }
+        Command::ListScrapers => {
+            for scraper in scrapers::registry().scrapers() {
+                println!(
+                    "{:<12} {} ({}), categories: {:?}",
+                    scraper.site_id, scraper.name, scraper.base_url, scraper.categories
+                );
+            }
+        }
+    }
 
     info!("Terminating application.");
 
@@ -0,0 +1,86 @@
+//! Detection of block pages served by anti-bot protections.
+//!
+//! Sites behind a WAF or a CDN may answer with a CAPTCHA, a "too many requests" or an
+//! "access denied" page instead of the requested page. WebDriver doesn't expose HTTP status
+//! codes, so block pages are recognized by text markers in their source.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Configuration for recognizing block pages and backing off when one is served.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockDetection {
+    /// Text markers, compared case-insensitively, whose presence in a page source marks it as
+    /// a block page.
+    pub markers: Vec<String>,
+    /// How many times a blocked page is fetched again before giving up.
+    pub max_retries: u32,
+    /// How long to wait before fetching a blocked page again the first time.
+    /// The wait is doubled for every following attempt.
+    pub initial_backoff: Duration,
+}
+
+impl Default for BlockDetection {
+    fn default() -> Self {
+        Self {
+            markers: vec![
+                "<title>Access Denied</title>".to_string(),
+                "<title>Too Many Requests</title>".to_string(),
+                "Attention Required! | Cloudflare".to_string(),
+                "cf-browser-verification".to_string(),
+                "/cdn-cgi/challenge-platform/".to_string(),
+            ],
+            max_retries: 3,
+            initial_backoff: Duration::from_secs(60),
+        }
+    }
+}
+
+impl BlockDetection {
+    /// Returns the first marker found in `source`, or `None` if it's not a block page.
+    pub fn find_marker(&self, source: &str) -> Option<&str> {
+        let source = source.to_lowercase();
+
+        self.markers
+            .iter()
+            .find(|m| source.contains(&m.to_lowercase()))
+            .map(String::as_str)
+    }
+
+    /// How long to wait before the retry numbered `attempt`, counting from 0.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff * 2u32.saturating_pow(attempt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_markers_ignoring_case() {
+        let detection = BlockDetection::default();
+
+        assert_eq!(
+            detection.find_marker("<html><TITLE>access denied</TITLE></html>"),
+            Some("<title>Access Denied</title>")
+        );
+        assert_eq!(
+            detection.find_marker("<html><title>Aer condiționat</title></html>"),
+            None
+        );
+    }
+
+    #[test]
+    fn backoff_doubles_with_every_attempt() {
+        let detection = BlockDetection {
+            initial_backoff: Duration::from_secs(60),
+            ..BlockDetection::default()
+        };
+
+        assert_eq!(detection.backoff(0), Duration::from_secs(60));
+        assert_eq!(detection.backoff(1), Duration::from_secs(120));
+        assert_eq!(detection.backoff(3), Duration::from_secs(480));
+    }
+}
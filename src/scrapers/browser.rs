@@ -0,0 +1,122 @@
+//! Connecting to a browser through WebDriver.
+
+use std::time::Duration;
+
+use fantoccini::error::NewSessionError;
+use fantoccini::Client;
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+/// URL of the WebDriver server used by all scrapers.
+pub const WEBDRIVER_URL: &str = "http://localhost:4444";
+
+/// How the browser is started. The defaults run it headless, at full speed.
+///
+/// The other options are meant for debugging, e.g. to see why a selector doesn't match on a
+/// live page.
+///
+/// Fields missing from options recorded by older versions are read as their defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BrowserOptions {
+    /// Show the browser window instead of running headless.
+    pub headed: bool,
+    /// Pause before every WebDriver command, to be able to follow what the scraper does.
+    pub slowmo: Option<Duration>,
+    /// Open the developer tools in every tab. Implies `headed`.
+    pub devtools: bool,
+    /// Record every WebDriver command in a trace file that can be replayed later.
+    /// See [trace](crate::scrapers::trace).
+    pub trace_commands: bool,
+    /// User agent to send instead of the browser's own. Set from the site's
+    /// [PolitenessProfile](crate::scrapers::crawl::PolitenessProfile).
+    pub user_agent: Option<String>,
+    /// HTTP proxy to connect through, as `host:port`. Set from the site's
+    /// [PolitenessProfile](crate::scrapers::crawl::PolitenessProfile).
+    pub proxy: Option<String>,
+    /// Languages the browser asks for in the `Accept-Language` header, e.g. `ro-RO,ro`, since
+    /// some sites serve different markup per language.
+    pub accept_language: Option<String>,
+}
+
+impl BrowserOptions {
+    /// Whether any debugging option is enabled.
+    ///
+    /// Scrapers log every WebDriver command at info level, instead of debug, when debugging.
+    pub fn is_debugging(&self) -> bool {
+        self.headed || self.slowmo.is_some() || self.devtools
+    }
+
+    /// WebDriver capabilities for these options, for both Chrome and Firefox.
+    pub fn capabilities(&self) -> Map<String, Value> {
+        let mut chrome_args = Vec::new();
+        let mut firefox_args = Vec::new();
+
+        if !self.headed && !self.devtools {
+            chrome_args.push("--headless".to_string());
+            firefox_args.push("-headless");
+        }
+
+        if self.devtools {
+            chrome_args.push("--auto-open-devtools-for-tabs".to_string());
+            firefox_args.push("-devtools");
+        }
+
+        let mut chrome_prefs = Map::new();
+        let mut firefox_prefs = Map::new();
+
+        if let Some(user_agent) = &self.user_agent {
+            chrome_args.push(format!("--user-agent={}", user_agent));
+            firefox_prefs.insert("general.useragent.override".to_string(), json!(user_agent));
+        }
+
+        if let Some(accept_language) = &self.accept_language {
+            chrome_prefs.insert("intl.accept_languages".to_string(), json!(accept_language));
+            firefox_prefs.insert("intl.accept_languages".to_string(), json!(accept_language));
+        }
+
+        let mut capabilities = Map::new();
+        capabilities.insert(
+            "goog:chromeOptions".to_string(),
+            json!({ "args": chrome_args, "prefs": chrome_prefs }),
+        );
+        capabilities.insert(
+            "moz:firefoxOptions".to_string(),
+            json!({ "args": firefox_args, "prefs": firefox_prefs }),
+        );
+
+        if let Some(proxy) = &self.proxy {
+            capabilities.insert(
+                "proxy".to_string(),
+                json!({ "proxyType": "manual", "httpProxy": proxy, "sslProxy": proxy }),
+            );
+        }
+
+        capabilities
+    }
+}
+
+/// Starts a new WebDriver session with a browser configured by `options`.
+///
+/// # Panics
+///
+/// Panics if the session can't be created, e.g. because no WebDriver server is running.
+pub fn connect(options: &BrowserOptions) -> Client {
+    match futures::executor::block_on(try_connect(options)) {
+        Ok(c) => c,
+        Err(e) => {
+            panic!(
+                "Failed to create new WebDriver session with {}: {}",
+                WEBDRIVER_URL, e
+            );
+        }
+    }
+}
+
+/// Like [connect], but fails instead of panicking if the session can't be created.
+pub async fn try_connect(options: &BrowserOptions) -> Result<Client, NewSessionError> {
+    info!("Starting WebDriver session with {:?}.", options);
+
+    Client::with_capabilities(WEBDRIVER_URL, options.capabilities()).await
+}
@@ -0,0 +1,207 @@
+//! A cache of fetched pages, shared by all scrapers.
+//!
+//! Pages are keyed by their normalized URL and stored as JSON files, one per page, together with
+//! the time they were fetched. A page older than the cache's time to live is treated as missing,
+//! so it gets fetched again.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::scrapers::frontier::normalize_url;
+use crate::scrapers::manifest::unix_timestamp;
+use crate::scrapers::{long_path, url_to_html_file_name, write_file_atomically};
+
+/// A page stored in the [PageCache].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedPage {
+    /// Normalized URL of the page.
+    pub url: String,
+    /// When the page was fetched, in seconds since the Unix epoch.
+    pub fetched_at: u64,
+    /// The page's source.
+    pub body: String,
+}
+
+/// Fetched pages stored on disk, with time based expiry.
+#[derive(Debug, Clone)]
+pub struct PageCache {
+    /// Folder the cached pages are stored in.
+    dir: PathBuf,
+    /// How long a cached page stays fresh.
+    ttl: Duration,
+}
+
+impl PageCache {
+    /// Creates a cache storing pages in the `dir` folder, which are fresh for `ttl`.
+    pub fn new(dir: &Path, ttl: Duration) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            ttl,
+        }
+    }
+
+    /// Path of the file `url` is cached in.
+    fn entry_path(&self, url: &Url) -> Result<PathBuf, String> {
+        let file_name = url_to_html_file_name(&normalize_url(url))?;
+
+        Ok(long_path(&self.dir.join(file_name).with_extension("json")))
+    }
+
+    /// Returns the cached page for `url`, or `None` if it isn't cached or has expired.
+    pub fn get(&self, url: &Url) -> Result<Option<CachedPage>, String> {
+        let path = self.entry_path(url)?;
+
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let json = std::fs::read(&path)
+            .map_err(|e| format!("Failed to read cached page {:?}: {}", path, e))?;
+        let page: CachedPage = serde_json::from_slice(&json)
+            .map_err(|e| format!("Failed to parse cached page {:?}: {}", path, e))?;
+
+        if unix_timestamp().saturating_sub(page.fetched_at) > self.ttl.as_secs() {
+            return Ok(None);
+        }
+
+        Ok(Some(page))
+    }
+
+    /// Stores `body` as the source of `url`, fetched just now.
+    pub fn put(&self, url: &Url, body: &str) -> Result<(), String> {
+        let path = self.entry_path(url)?;
+        let page = CachedPage {
+            url: normalize_url(url).to_string(),
+            fetched_at: unix_timestamp(),
+            body: body.to_string(),
+        };
+        let json = serde_json::to_vec(&page).map_err(|e| e.to_string())?;
+
+        std::fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create cache folder {:?}: {}", self.dir, e))?;
+
+        write_file_atomically(&path, &json)
+            .map_err(|e| format!("Failed to write cached page {:?}: {}", path, e))
+    }
+
+    /// Deletes the cached pages that have expired, and those that can't be read. Pages cached
+    /// by other sites' runs are pruned too, as they expire by the same time to live.
+    ///
+    /// Returns the number of pages deleted.
+    pub fn prune_expired(&self) -> Result<u32, String> {
+        if !self.dir.exists() {
+            return Ok(0);
+        }
+
+        let entries = std::fs::read_dir(&self.dir)
+            .map_err(|e| format!("Failed to read cache folder {:?}: {}", self.dir, e))?;
+        let mut pruned = 0;
+
+        for entry in entries {
+            let path = entry
+                .map_err(|e| format!("Failed to read cache folder {:?}: {}", self.dir, e))?
+                .path();
+
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+
+            let expired = match std::fs::read(&path)
+                .ok()
+                .and_then(|json| serde_json::from_slice::<CachedPage>(&json).ok())
+            {
+                Some(page) => unix_timestamp().saturating_sub(page.fetched_at) > self.ttl.as_secs(),
+                None => true,
+            };
+
+            if expired {
+                std::fs::remove_file(&path)
+                    .map_err(|e| format!("Failed to delete cached page {:?}: {}", path, e))?;
+
+                pruned += 1;
+            }
+        }
+
+        Ok(pruned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty cache folder for the test named `name`.
+    fn cache_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("page-cache-{}-{}", name, std::process::id()));
+
+        let _ = std::fs::remove_dir_all(&dir);
+
+        dir
+    }
+
+    #[test]
+    fn returns_fresh_pages_by_normalized_url() {
+        let cache = PageCache::new(&cache_dir("fresh"), Duration::from_secs(60));
+        let url = Url::parse("https://www.climatico.ro/ac?p=2&a=1#top").unwrap();
+
+        cache.put(&url, "<html></html>").unwrap();
+
+        let equivalent_url = Url::parse("https://www.climatico.ro/ac?a=1&p=2").unwrap();
+        let page = cache.get(&equivalent_url).unwrap().unwrap();
+
+        assert_eq!(page.body, "<html></html>");
+        assert_eq!(page.url, "https://www.climatico.ro/ac?a=1&p=2");
+    }
+
+    #[test]
+    fn treats_expired_and_uncached_pages_as_missing() {
+        let cache = PageCache::new(&cache_dir("expired"), Duration::from_secs(60));
+        let url = Url::parse("https://www.climatico.ro/ac").unwrap();
+        let page = CachedPage {
+            url: url.to_string(),
+            fetched_at: unix_timestamp() - 61,
+            body: "<html></html>".to_string(),
+        };
+
+        std::fs::create_dir_all(&cache.dir).unwrap();
+        std::fs::write(
+            cache.entry_path(&url).unwrap(),
+            serde_json::to_vec(&page).unwrap(),
+        )
+        .unwrap();
+
+        assert!(cache.get(&url).unwrap().is_none());
+        assert!(cache
+            .get(&Url::parse("https://www.climatico.ro/other").unwrap())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn prunes_expired_and_unreadable_pages() {
+        let cache = PageCache::new(&cache_dir("prune"), Duration::from_secs(60));
+        let fresh_url = Url::parse("https://www.climatico.ro/fresh").unwrap();
+        let expired_url = Url::parse("https://www.climatico.ro/expired").unwrap();
+        let expired_page = CachedPage {
+            url: expired_url.to_string(),
+            fetched_at: unix_timestamp() - 61,
+            body: "<html></html>".to_string(),
+        };
+
+        cache.put(&fresh_url, "<html></html>").unwrap();
+        std::fs::write(
+            cache.entry_path(&expired_url).unwrap(),
+            serde_json::to_vec(&expired_page).unwrap(),
+        )
+        .unwrap();
+        std::fs::write(cache.dir.join("broken.json"), "{").unwrap();
+
+        assert_eq!(cache.prune_expired(), Ok(2));
+        assert!(cache.entry_path(&fresh_url).unwrap().exists());
+        assert!(!cache.entry_path(&expired_url).unwrap().exists());
+        assert!(!cache.dir.join("broken.json").exists());
+    }
+}
@@ -0,0 +1,117 @@
+//! Decoding of page sources saved in encodings other than UTF-8.
+//!
+//! Pages fetched through the browser are always UTF-8, but page sources saved by older tools,
+//! or copied into the output folder by hand, may be in the charset the site declared, like
+//! ISO-8859-2, or contain bytes that are not valid UTF-8. WebDriver doesn't expose HTTP headers,
+//! so the charset is taken from a byte order mark or a `<meta>` tag near the top of the page.
+
+use encoding_rs::{Encoding, UTF_8};
+
+/// How many bytes from the start of a page are searched for a `<meta>` charset declaration.
+/// Browsers only look this far too.
+const META_PRESCAN_LEN: usize = 1024;
+
+/// A page source decoded to UTF-8.
+#[derive(Debug)]
+pub struct DecodedSource {
+    /// The page source as UTF-8.
+    pub text: String,
+    /// Whether the source wasn't plain UTF-8, because it was transcoded from another charset
+    /// or had invalid bytes replaced with `U+FFFD`.
+    pub mis_encoded: bool,
+}
+
+/// Returns the charset declared by `source`'s byte order mark or by a `<meta charset="...">` or
+/// `<meta http-equiv="Content-Type" content="...; charset=...">` tag.
+pub fn declared_charset(source: &[u8]) -> Option<&'static Encoding> {
+    if let Some((encoding, _)) = Encoding::for_bom(source) {
+        return Some(encoding);
+    }
+
+    let head = &source[..source.len().min(META_PRESCAN_LEN)];
+    let head = String::from_utf8_lossy(head).to_lowercase();
+
+    head.match_indices("charset=").find_map(|(i, m)| {
+        let label: String = head[i + m.len()..]
+            .trim_start_matches(|c| c == '"' || c == '\'')
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || "-_.:".contains(*c))
+            .collect();
+
+        // A page declaring UTF-16 in a `<meta>` tag can't be UTF-16, since the tag was read as
+        // ASCII, so it's treated as UTF-8 as browsers do.
+        Encoding::for_label(label.as_bytes()).map(Encoding::output_encoding)
+    })
+}
+
+/// Decodes `source` to UTF-8 using its [declared_charset], or UTF-8 if it doesn't declare one.
+/// Bytes that are invalid in that charset are replaced with `U+FFFD`.
+///
+/// A source that is valid UTF-8 is kept as is whatever it declares, because the browser
+/// serializes pages as UTF-8 but leaves their `<meta>` tags alone.
+pub fn decode_page_source(source: &[u8]) -> DecodedSource {
+    if let Ok(text) = std::str::from_utf8(source) {
+        return DecodedSource {
+            text: text.to_string(),
+            mis_encoded: false,
+        };
+    }
+
+    let encoding = declared_charset(source).unwrap_or(UTF_8);
+    let (text, actual_encoding, had_errors) = encoding.decode(source);
+
+    DecodedSource {
+        mis_encoded: had_errors || actual_encoding != UTF_8,
+        text: text.into_owned(),
+    }
+}
+
+/// Reads the page source at `path` and decodes it with [decode_page_source].
+pub fn read_page_source(path: &std::path::Path) -> std::io::Result<DecodedSource> {
+    Ok(decode_page_source(&std::fs::read(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_valid_utf8_whatever_it_declares() {
+        let source = "<meta charset=\"iso-8859-2\"><p>Aer condiționat</p>";
+        let decoded = decode_page_source(source.as_bytes());
+
+        assert_eq!(decoded.text, source);
+        assert!(!decoded.mis_encoded);
+    }
+
+    #[test]
+    fn decodes_the_declared_charset() {
+        // `ş` is 0xBA in ISO-8859-2.
+        let mut source = b"<meta charset=\"iso-8859-2\"><p>".to_vec();
+        source.extend_from_slice(&[0x70, 0x72, 0x65, 0xBA]);
+
+        let decoded = decode_page_source(&source);
+
+        assert_eq!(decoded.text, "<meta charset=\"iso-8859-2\"><p>pre\u{15f}");
+        assert!(decoded.mis_encoded);
+    }
+
+    #[test]
+    fn replaces_invalid_utf8_without_a_declared_charset() {
+        let decoded = decode_page_source(b"<p>pre\xff</p>");
+
+        assert_eq!(decoded.text, "<p>pre\u{fffd}</p>");
+        assert!(decoded.mis_encoded);
+    }
+
+    #[test]
+    fn reads_the_charset_of_a_content_type_meta_tag() {
+        let source =
+            b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=windows-1250\">";
+
+        assert_eq!(
+            declared_charset(source).map(Encoding::name),
+            Some("windows-1250")
+        );
+    }
+}
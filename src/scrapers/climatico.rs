@@ -1,64 +1,675 @@
 //! A module for scraping `https://www.climatico.ro/`.
 
+use std::collections::{HashMap, HashSet};
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::{Duration, Instant};
 
-use fantoccini::{Client, Locator};
-use log::{error, info};
+use fantoccini::error::CmdError;
+use fantoccini::Client;
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+use log::{debug, error, info, log, warn, Level};
+use select::document::Document;
+use select::predicate::{Class, Name, Predicate};
+use serde_json::Value;
 use url::Url;
 
-use crate::scrapers::url_to_html_file_name;
+use crate::scrapers::anti_bot::BlockDetection;
+use crate::scrapers::browser::{self, BrowserOptions};
+use crate::scrapers::cache::PageCache;
+use crate::scrapers::charset::read_page_source;
+use crate::scrapers::crawl::{CrawlConfig, CrawlReport, LayoutAnchor, NavigationPacer};
+use crate::scrapers::data::{currency_of_price, Currency, ProductCategory};
+use crate::scrapers::fetch::FetchClient;
+use crate::scrapers::frontier::{normalize_url, Frontier, LISTING_PAGE_PRIORITY};
+use crate::scrapers::registry::{ScraperInfo, SiteScraper};
+use crate::scrapers::selector_healing::suggest_selectors;
+use crate::scrapers::trace::{CommandOutput, TraceWriter, TracedCommand};
+use crate::scrapers::{
+    find_next_page_url, find_pagination_next_url, harvest_links, has_pager, long_path, page_number,
+    url_to_html_file_name, with_page_number, write_file_atomically, VISITED_URLS_FILE_NAME,
+};
 
-pub struct ClimaticoScraper<'a> {
-    client: fantoccini::Client,
+/// Id of this site, used to select it on the command line and to namespace its output.
+pub const SITE_ID: &str = "climatico";
+
+/// URLs of the first page of each product listing crawled on this site.
+pub const LISTING_URLS: &[&str] = &["https://www.climatico.ro/aer-conditionat/comercial"];
+
+/// Describes this scraper for the [crate::scrapers::registry()].
+pub fn scraper_info() -> ScraperInfo {
+    ScraperInfo {
+        site_id: SITE_ID,
+        name: "Climatico",
+        base_url: "https://www.climatico.ro/",
+        categories: vec![ProductCategory::AirConditioner],
+        listing_urls: LISTING_URLS,
+        product_list_limit: Some("36"),
+        newest_first_order: Some("created_at"),
+        count_products: count_product_nodes,
+        new_scraper,
+    }
+}
+
+/// Creates a [ClimaticoScraper], see [ScraperInfo::new_scraper].
+fn new_scraper<'a>(
+    client: Client,
+    page_sources_output_path: &'a Path,
+    product_info_output_path: &'a Path,
+    browser_options: BrowserOptions,
+) -> Box<dyn SiteScraper + 'a> {
+    Box::new(ClimaticoScraper::with_client(
+        client,
+        page_sources_output_path,
+        product_info_output_path,
+        browser_options,
+    ))
+}
+
+/// Currency this site's prices are shown in. Another currency means the site is serving a
+/// storefront for another country, e.g. to a VPN or proxy, so its prices are wrong for us.
+pub const EXPECTED_CURRENCY: Currency = Currency::RON;
+
+/// Elements expected on every page of a product listing.
+pub const LISTING_PAGE_ANCHORS: &[(&str, &str)] = &[
+    ("product list", "ol.product-items"),
+    ("product price", "ol.product-items .price-box .price"),
+    ("listing toolbar", ".toolbar-products"),
+];
+
+/// Counts the product nodes in the `source` of a listing page.
+pub fn count_product_nodes(source: &str) -> usize {
+    Document::from(source)
+        .find(Name("li").and(Class("product-item")))
+        .count()
+}
+
+/// Returns the product page URLs on a listing page's `source`, in order, to tell pages of a
+/// listing apart.
+fn product_links(source: &str) -> Vec<String> {
+    Document::from(source)
+        .find(Class("product-item-link"))
+        .filter_map(|n| n.attr("href"))
+        .map(String::from)
+        .collect()
+}
+
+/// Checks the currency of the first price on a listing page's `source` against
+/// [EXPECTED_CURRENCY].
+///
+/// Returns `None` if the page has no price whose currency can be read, so another page has to
+/// be checked, or a description of the problem if the currency is wrong.
+fn check_currency(source: &str) -> Option<Result<(), String>> {
+    let document = Document::from(source);
+    let price = document
+        .find(Class("price-box").descendant(Class("price")))
+        .next()?
+        .text();
+
+    match currency_of_price(&price) {
+        Ok(c) if c == EXPECTED_CURRENCY => Some(Ok(())),
+        Ok(c) => Some(Err(format!(
+            "The site shows prices in {:?} instead of {:?}, e.g. {:?}. Is a VPN or proxy \
+             serving another country's storefront?",
+            c,
+            EXPECTED_CURRENCY,
+            price.trim()
+        ))),
+        Err(_) => None,
+    }
+}
+
+/// How often to check whether a page finished rendering.
+const RENDER_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Script returning the page's ready state, the number of pending jQuery requests and the number
+/// of resources loaded so far.
+const NETWORK_STATE_SCRIPT: &str = "return [document.readyState, \
+    window.jQuery ? window.jQuery.active : 0, \
+    performance.getEntriesByType('resource').length];";
+
+pub struct ClimaticoScraper<'a, C: FetchClient = Client> {
+    client: C,
+    /// How the browser was started.
+    browser_options: BrowserOptions,
     /// Folder path for saving web page sources to disk.
     /// Very useful so web page don't need to be fetched every time
     /// when scraping new resources from them.
     page_sources_output_path: &'a Path,
     /// Folder path for saving scraped product information to disk.
     product_info_output_path: &'a Path,
+    /// How block pages are recognized and retried.
+    block_detection: BlockDetection,
+    /// Limits and other settings for crawling product listings.
+    crawl_config: CrawlConfig,
+    /// URLs queued and visited during this run, shared by all crawled listings.
+    /// Created by the first crawl.
+    frontier: Option<Frontier>,
+    /// File the frontier records visited URLs in. Defaults to [VISITED_URLS_FILE_NAME] in the
+    /// page sources folder.
+    visited_urls_file_path: Option<PathBuf>,
+    /// Trace file recording the WebDriver commands issued, if enabled.
+    trace: Option<TraceWriter>,
+    /// Cache of fetched pages, if enabled.
+    page_cache: Option<PageCache>,
+    /// Links found while crawling, with the number of pages linking to each, when discovery is
+    /// enabled.
+    discovered_urls: HashMap<String, u32>,
+    /// What was crawled so far.
+    report: CrawlReport,
+    /// Elements expected on every listing page, to detect layout changes.
+    layout_anchors: Vec<LayoutAnchor>,
+    /// Spaces out navigations by the politeness profile's delay.
+    navigation_pacer: NavigationPacer,
+    /// Whether a fetched page showed prices in the [EXPECTED_CURRENCY].
+    currency_checked: bool,
+    /// Product page URLs found by earlier runs.
+    seen_products: HashSet<String>,
+    /// Product page URLs found by this run and not by earlier ones.
+    new_products: HashSet<String>,
+    /// File recording the product page URLs found, if enabled.
+    seen_products_file_path: Option<PathBuf>,
+}
+
+/// [LISTING_PAGE_ANCHORS] as [LayoutAnchor]s.
+fn default_layout_anchors() -> Vec<LayoutAnchor> {
+    LISTING_PAGE_ANCHORS
+        .iter()
+        .map(|(name, selector)| LayoutAnchor::new(name, selector))
+        .collect()
 }
 
-impl<'a> Default for ClimaticoScraper<'a> {
+impl<'a> Default for ClimaticoScraper<'a, Client> {
     fn default() -> Self {
         info!("Creating ClimaticoScraper using default configuration.");
 
-        let client_future = Client::new("http://localhost:4444");
-        let client = match futures::executor::block_on(client_future) {
-            Ok(c) => c,
-            // TODO: display error message in panic.
-            Err(_) => {
-                panic!("Failed to create new WebDriver session with http://localhost:4444.");
-            }
-        };
+        let browser_options = BrowserOptions::default();
+        let client = browser::connect(&browser_options);
 
         Self {
             client,
+            browser_options,
             page_sources_output_path: Path::new("./"),
             product_info_output_path: Path::new("./"),
+            block_detection: BlockDetection::default(),
+            crawl_config: CrawlConfig::default(),
+            frontier: None,
+            visited_urls_file_path: None,
+            trace: None,
+            page_cache: None,
+            discovered_urls: HashMap::new(),
+            report: CrawlReport::default(),
+            layout_anchors: default_layout_anchors(),
+            navigation_pacer: NavigationPacer::default(),
+            currency_checked: false,
+            seen_products: HashSet::new(),
+            new_products: HashSet::new(),
+            seen_products_file_path: None,
         }
     }
 }
 
-impl<'a> ClimaticoScraper<'a> {
-    pub fn new(page_sources_output_path: &'a str, product_info_output_path: &'a str) -> Self {
+impl<'a> ClimaticoScraper<'a, Client> {
+    pub fn new<P: AsRef<Path> + ?Sized>(
+        page_sources_output_path: &'a P,
+        product_info_output_path: &'a P,
+    ) -> Self {
+        Self::with_browser_options(
+            page_sources_output_path,
+            product_info_output_path,
+            BrowserOptions::default(),
+        )
+    }
+
+    /// Like [new](ClimaticoScraper::new), but starts the browser with `browser_options`.
+    pub fn with_browser_options<P: AsRef<Path> + ?Sized>(
+        page_sources_output_path: &'a P,
+        product_info_output_path: &'a P,
+        browser_options: BrowserOptions,
+    ) -> Self {
+        let client = browser::connect(&browser_options);
+
+        ClimaticoScraper::with_client(
+            client,
+            page_sources_output_path,
+            product_info_output_path,
+            browser_options,
+        )
+    }
+}
+
+impl<'a, C: FetchClient> ClimaticoScraper<'a, C> {
+    /// Creates a scraper that fetches pages with `client`, which was started with
+    /// `browser_options`.
+    pub fn with_client<P: AsRef<Path> + ?Sized>(
+        client: C,
+        page_sources_output_path: &'a P,
+        product_info_output_path: &'a P,
+        browser_options: BrowserOptions,
+    ) -> Self {
         info!("Creating ClimaticoScraper.");
 
-        let client_future = Client::new("http://localhost:4444");
-        let client = match futures::executor::block_on(client_future) {
-            Ok(c) => c,
-            // TODO: display error message in panic.
-            Err(_) => {
-                panic!("Failed to create new WebDriver session with http://localhost:4444.");
+        Self {
+            client,
+            browser_options,
+            page_sources_output_path: page_sources_output_path.as_ref(),
+            product_info_output_path: product_info_output_path.as_ref(),
+            block_detection: BlockDetection::default(),
+            crawl_config: CrawlConfig::default(),
+            frontier: None,
+            visited_urls_file_path: None,
+            trace: None,
+            page_cache: None,
+            discovered_urls: HashMap::new(),
+            report: CrawlReport::default(),
+            layout_anchors: default_layout_anchors(),
+            navigation_pacer: NavigationPacer::default(),
+            currency_checked: false,
+            seen_products: HashSet::new(),
+            new_products: HashSet::new(),
+            seen_products_file_path: None,
+        }
+    }
+
+    /// The client used to fetch pages.
+    pub fn client(&self) -> &C {
+        &self.client
+    }
+
+    /// Folder path where web page sources are saved.
+    pub fn page_sources_output_path(&self) -> &Path {
+        self.page_sources_output_path
+    }
+
+    /// Folder path where scraped product information is saved.
+    pub fn product_info_output_path(&self) -> &Path {
+        self.product_info_output_path
+    }
+
+    /// What was crawled so far, over all product listings.
+    pub fn report(&self) -> &CrawlReport {
+        &self.report
+    }
+
+    pub fn discovered_urls(&self) -> &HashMap<String, u32> {
+        &self.discovered_urls
+    }
+
+    /// Replaces the default configuration for recognizing block pages.
+    pub fn set_block_detection(&mut self, block_detection: BlockDetection) {
+        self.block_detection = block_detection;
+    }
+
+    /// Replaces the elements expected on every listing page.
+    pub fn set_layout_anchors(&mut self, layout_anchors: Vec<LayoutAnchor>) {
+        self.layout_anchors = layout_anchors;
+    }
+
+    /// Replaces the default crawl configuration.
+    pub fn set_crawl_config(&mut self, crawl_config: CrawlConfig) {
+        self.crawl_config = crawl_config;
+    }
+
+    /// Records the URLs visited by crawls in the file at `path`, instead of the default
+    /// [VISITED_URLS_FILE_NAME] in the page sources folder.
+    pub fn set_visited_urls_file(&mut self, path: &Path) {
+        self.visited_urls_file_path = Some(path.to_path_buf());
+    }
+
+    /// Treats `seen_products` as found by earlier runs and records the product page URLs found
+    /// by this scraper that aren't among them in the file at `path`, one per line.
+    ///
+    /// With [CrawlConfig::new_arrivals], a listing's crawl stops at its first page showing a
+    /// product from `seen_products`.
+    pub fn set_seen_products(&mut self, seen_products: HashSet<String>, path: &Path) {
+        self.seen_products = seen_products;
+        self.seen_products_file_path = Some(path.to_path_buf());
+    }
+
+    /// Spaces out navigations with `navigation_pacer`, shared with the other scrapers crawling
+    /// the same site, instead of on this scraper's own.
+    pub fn set_navigation_pacer(&mut self, navigation_pacer: NavigationPacer) {
+        self.navigation_pacer = navigation_pacer;
+    }
+
+    /// Reuses pages from `page_cache` instead of fetching them, while they're fresh, and caches
+    /// fetched pages in it.
+    pub fn set_page_cache(&mut self, page_cache: PageCache) {
+        self.page_cache = Some(page_cache);
+    }
+
+    /// Records WebDriver commands issued by this scraper in the trace file at `path`.
+    pub fn start_trace(&mut self, path: &Path) -> std::io::Result<()> {
+        info!("Recording WebDriver commands to {:?}.", path);
+
+        self.trace = Some(TraceWriter::create(path)?);
+
+        Ok(())
+    }
+
+    /// Issues a WebDriver command.
+    ///
+    /// The command is logged, delayed in slow motion and recorded in the trace, if there is one.
+    async fn run_command(&mut self, command: TracedCommand) -> Result<CommandOutput, CmdError> {
+        let level = if self.browser_options.is_debugging() {
+            Level::Info
+        } else {
+            Level::Debug
+        };
+
+        log!(level, "WebDriver command: {:?}", command);
+
+        if let Some(slowmo) = self.browser_options.slowmo {
+            tokio::timer::delay_for(slowmo).await;
+        }
+
+        let issued_at = Instant::now();
+        let result = command.issue(&mut self.client).await;
+
+        if let Some(trace) = &mut self.trace {
+            trace
+                .record(&command, issued_at, result.is_ok())
+                .expect("Failed to write to the WebDriver trace file.");
+        }
+
+        result
+    }
+
+    /// Navigates to `url`, first waiting out the delay of the politeness profile for its host.
+    async fn goto(&mut self, url: &Url) -> Result<(), CmdError> {
+        let delay = self.crawl_config.politeness_for(url).delay;
+        let wait = self.navigation_pacer.schedule(url, delay);
+
+        if wait > Duration::from_secs(0) {
+            debug!("Waiting {:?} before navigating to {}.", wait, url);
+
+            tokio::timer::delay_for(wait).await;
+        }
+
+        self.run_command(TracedCommand::Goto {
+            url: url.to_string(),
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns the current page's source.
+    async fn source(&mut self) -> Result<String, CmdError> {
+        match self.run_command(TracedCommand::Source).await? {
+            CommandOutput::Source(s) => Ok(s),
+            o => unreachable!("source returned {:?}", o),
+        }
+    }
+
+    /// Checks whether an element matching the CSS `selector` is on the current page.
+    async fn has_element(&mut self, selector: &str) -> Result<bool, CmdError> {
+        let command = TracedCommand::Find {
+            selector: selector.to_string(),
+        };
+
+        match self.run_command(command).await? {
+            CommandOutput::Found(found) => Ok(found),
+            o => unreachable!("find returned {:?}", o),
+        }
+    }
+
+    /// Executes `script` in the current page and returns its result.
+    async fn execute(&mut self, script: &str) -> Result<Value, CmdError> {
+        let command = TracedCommand::Execute {
+            script: script.to_string(),
+        };
+
+        match self.run_command(command).await? {
+            CommandOutput::Value(v) => Ok(v),
+            o => unreachable!("execute returned {:?}", o),
+        }
+    }
+
+    /// Waits for the current page to finish rendering, as configured by the crawl's
+    /// [RenderWait](crate::scrapers::crawl::RenderWait).
+    async fn wait_until_rendered(&mut self, page_url: &Url) -> Result<(), CmdError> {
+        let render_wait = self.crawl_config.render_wait.clone();
+        let started_at = Instant::now();
+
+        if let Some(selector) = &render_wait.selector {
+            loop {
+                if self.has_element(selector).await? {
+                    break;
+                }
+
+                if started_at.elapsed() >= render_wait.timeout {
+                    warn!(
+                        "Timed out waiting for {:?} on page {}. Saving it anyway.",
+                        selector, page_url
+                    );
+
+                    break;
+                }
+
+                tokio::timer::delay_for(RENDER_POLL_INTERVAL).await;
+            }
+        }
+
+        if render_wait.network_idle {
+            let mut last_state = None;
+
+            loop {
+                let state = self.execute(NETWORK_STATE_SCRIPT).await?;
+                let is_idle =
+                    state[0] == "complete" && state[1] == 0 && last_state == Some(state.clone());
+
+                if is_idle {
+                    break;
+                }
+
+                if started_at.elapsed() >= render_wait.timeout {
+                    warn!(
+                        "Timed out waiting for network idle on page {}. Saving it anyway.",
+                        page_url
+                    );
+
+                    break;
+                }
+
+                last_state = Some(state);
+
+                tokio::timer::delay_for(RENDER_POLL_INTERVAL).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the current page has all the layout anchors, counting missing ones in the
+    /// report.
+    async fn check_layout_anchors(&mut self, page_url: &Url, source: &str) -> Result<(), CmdError> {
+        for anchor in self.layout_anchors.clone() {
+            if !self.has_element(&anchor.selector).await? {
+                let suggestions = suggest_selectors(source, &anchor.selector);
+
+                warn!(
+                    "Page {} has no {} ({:?}). Similar elements: {:?}.",
+                    page_url, anchor.name, anchor.selector, suggestions
+                );
+
+                self.report
+                    .add_selector_suggestions(&anchor.name, &suggestions);
+                *self.report.missing_anchors.entry(anchor.name).or_insert(0) += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Navigates to `page_url` and returns its source.
+    ///
+    /// While a block page is served, waits with an exponential backoff and tries again.
+    /// Returns `None` if the page is still blocked after all retries.
+    async fn fetch_page_source(&mut self, page_url: &Url) -> Result<Option<String>, CmdError> {
+        let mut attempt = 0;
+
+        loop {
+            info!("Navigating to page {:?}", page_url);
+
+            self.goto(page_url).await?;
+
+            self.wait_until_rendered(page_url).await?;
+
+            let source = self.source().await?;
+
+            let marker = match self.block_detection.find_marker(&source) {
+                None => return Ok(Some(source)),
+                Some(m) => m.to_string(),
+            };
+
+            if attempt >= self.block_detection.max_retries {
+                error!(
+                    "Page {} is still blocked after {} retries (found marker {:?}).",
+                    page_url, attempt, marker
+                );
+
+                return Ok(None);
             }
+
+            let backoff = self.block_detection.backoff(attempt);
+
+            warn!(
+                "Page {} looks like a block page (found marker {:?}). Retrying in {:?}.",
+                page_url, marker, backoff
+            );
+
+            tokio::timer::delay_for(backoff).await;
+
+            attempt += 1;
+        }
+    }
+
+    /// Fetches a page of a product listing like [Self::fetch_page_source], fetching it once more
+    /// if it has no products. Pages still without products mark the crawl as degraded. Returns
+    /// `None` if the site blocks either fetch.
+    ///
+    /// The layout anchors are checked once, on the page as it was finally fetched.
+    ///
+    /// Until a page shows prices in the [EXPECTED_CURRENCY], the currency is checked. A page in
+    /// another currency is recorded in [CrawlReport::wrong_currency] and `None` is returned.
+    ///
+    /// Pages with products are stored in the page cache, if enabled.
+    async fn fetch_listing_page(&mut self, page_url: &Url) -> Result<Option<String>, CmdError> {
+        let mut source = match self.fetch_page_source(page_url).await? {
+            Some(s) => s,
+            None => return Ok(None),
         };
 
-        Self {
-            client,
-            page_sources_output_path: Path::new(page_sources_output_path),
-            product_info_output_path: Path::new(product_info_output_path),
+        if count_product_nodes(&source) == 0 {
+            warn!("Page {} has no products. Fetching it again.", page_url);
+
+            source = match self.fetch_page_source(page_url).await? {
+                Some(s) => s,
+                None => return Ok(None),
+            };
+        }
+
+        self.check_layout_anchors(page_url, &source).await?;
+
+        if count_product_nodes(&source) == 0 {
+            error!(
+                "Page {} still has no products. Marking the crawl as degraded.",
+                page_url
+            );
+
+            self.report.pages_without_products += 1;
+            self.report.degraded = true;
+
+            return Ok(Some(source));
+        }
+
+        if !self.currency_checked {
+            match check_currency(&source) {
+                Some(Ok(())) => self.currency_checked = true,
+                Some(Err(e)) => {
+                    error!("Page {}: {}", page_url, e);
+
+                    self.report.wrong_currency = Some(e);
+                    self.report.degraded = true;
+
+                    return Ok(None);
+                }
+                None => {}
+            }
+        }
+
+        if let Some(cache) = &self.page_cache {
+            cache
+                .put(page_url, &source)
+                .unwrap_or_else(|e| warn!("{}", e));
+        }
+
+        Ok(Some(source))
+    }
+
+    /// Appends the URLs in `products` that weren't seen before to the seen products file, if
+    /// enabled.
+    fn record_new_products(&mut self, products: &[String]) -> std::io::Result<()> {
+        let path = match &self.seen_products_file_path {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+        let mut lines = String::new();
+
+        for product in products {
+            if !self.seen_products.contains(product) && self.new_products.insert(product.clone()) {
+                lines.push_str(product);
+                lines.push('\n');
+            }
+        }
+
+        if lines.is_empty() {
+            return Ok(());
+        }
+
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?
+            .write_all(lines.as_bytes())
+    }
+
+    /// Saves the source of the page at each of `page_urls` as it is, without following its
+    /// pagination or discovering other pages. Stops if the site starts blocking us.
+    pub async fn save_pages(&mut self, page_urls: &[Url]) -> Result<(), CmdError> {
+        std::fs::create_dir_all(self.page_sources_output_path)
+            .expect("Failed to create directory structure.");
+
+        for page_url in page_urls {
+            let source_file_name = url_to_html_file_name(page_url)
+                .expect("Failed to determine path for source file from its URL.");
+            let source_file_pathbuf =
+                long_path(&self.page_sources_output_path.join(&source_file_name));
+
+            let source = match self.fetch_page_source(page_url).await? {
+                Some(s) => s,
+                None => {
+                    error!("Stopping, the site is blocking us. Block page not saved.");
+
+                    self.report.pages_blocked += 1;
+
+                    break;
+                }
+            };
+
+            info!("Writing source file to disk: {:?}", source_file_pathbuf);
+
+            write_file_atomically(source_file_pathbuf.as_path(), source.as_ref())
+                .expect("Failed to write page source to disk.");
+
+            self.report.pages_saved += 1;
+            self.report.files.push(source_file_name);
         }
+
+        Ok(())
     }
 
     pub async fn save_page_sources(
@@ -67,68 +678,300 @@ impl<'a> ClimaticoScraper<'a> {
     ) -> Result<(), fantoccini::error::CmdError> {
         info!("Saving page sources starting with {}", first_page_url);
 
-        let mut page_url = Url::from_str(first_page_url)
+        let first_page_url = Url::from_str(first_page_url)
             .expect("Failed to parse argument first_page_url into a valid URL.");
 
+        info!("Creating page_sources_output_path directory structure, if it's missing.");
+
+        std::fs::create_dir_all(self.page_sources_output_path)
+            .expect("Failed to create directory structure.");
+
+        let mut frontier = match self.frontier.take() {
+            Some(f) => f,
+            None => Frontier::with_visited_file(
+                &self
+                    .visited_urls_file_path
+                    .clone()
+                    .unwrap_or_else(|| self.page_sources_output_path.join(VISITED_URLS_FILE_NAME)),
+                self.crawl_config.resume,
+            )
+            .expect("Failed to open the visited URLs file."),
+        };
+
+        frontier.push(&first_page_url, LISTING_PAGE_PRIORITY);
+
+        let limits = self.crawl_config.limits.clone();
+        let started_at = Instant::now();
+        let mut pages_saved = 0;
+        let mut products_found = 0;
+        // Pages queued by probing, see below, with the products of the page they were probed
+        // from.
+        let mut probed_from: HashMap<String, Vec<String>> = HashMap::new();
+
         // Navigate to each page of the product listing and save the pages to disk:
-        loop {
-            let source_file_pathbuf = match url_to_html_file_name(&page_url) {
-                Ok(p) => self.page_sources_output_path.join(p),
+        while let Some(page_url) = frontier.pop() {
+            if let Some(max_duration) = limits.max_duration {
+                if started_at.elapsed() >= max_duration {
+                    info!("Reached the crawl duration limit of {:?}.", max_duration);
+
+                    break;
+                }
+            }
+
+            let source_file_name = match url_to_html_file_name(&page_url) {
+                Ok(p) => p,
                 Err(e) => {
                     error!(
                         "Failed to determine path for source file from its URL: {}",
                         e
                     );
-                    panic!(e);
+                    panic!("{}", e);
                 }
             };
+            let source_file_pathbuf =
+                long_path(&self.page_sources_output_path.join(&source_file_name));
 
-            info!("Creating page_sources_output_path directory structure, if it's missing.");
+            let source = if frontier.is_visited(&page_url) && source_file_pathbuf.exists() {
+                info!(
+                    "Page {} was saved by the resumed crawl. Reading it from disk.",
+                    page_url
+                );
 
-            std::fs::create_dir_all(self.page_sources_output_path)
-                .expect("Failed to create directory structure.");
+                let decoded = read_page_source(&source_file_pathbuf)
+                    .expect("Failed to read saved page source.");
 
-            info!("Navigating to page {:?}", page_url);
+                if decoded.mis_encoded {
+                    warn!(
+                        "Page source {:?} is not valid UTF-8. Decoded it lossily.",
+                        source_file_pathbuf
+                    );
+                    self.report.pages_mis_encoded += 1;
+                }
 
-            self.client.goto(page_url.as_ref()).await?;
+                self.report.pages_reused += 1;
+                self.report.files.push(source_file_name);
 
-            let source = self.client.source().await?;
-            let mut source_file = std::fs::File::create(source_file_pathbuf.as_path())
-                .expect("Failed to create file for page source.");
+                decoded.text
+            } else {
+                let cached_page = match &self.page_cache {
+                    Some(cache) if !self.crawl_config.refresh => {
+                        cache.get(&page_url).unwrap_or_else(|e| {
+                            warn!("{}. Fetching the page instead.", e);
+                            None
+                        })
+                    }
+                    _ => None,
+                };
 
-            info!("Writing source file to disk: {:?}", source_file_pathbuf);
+                let source = match cached_page {
+                    Some(page) => {
+                        info!("Page {} was found in the page cache.", page_url);
 
-            source_file
-                .write_all(source.as_ref())
-                .expect("Failed to write page source to disk.");
+                        self.report.pages_cached += 1;
 
-            match self
-                .client
-                .find(Locator::Css("head > link[rel=next]"))
-                .await
-            {
-                Ok(mut link) => {
-                    let link_url = link
-                        .attr("href")
-                        .await?
-                        .expect("link tag with rel=next should have an href attribute!");
+                        page.body
+                    }
+                    None => {
+                        let fetched = match probed_from.remove(page_url.as_str()) {
+                            // A probed page past the last one is empty, or Magento serves the
+                            // last page again. Neither is saved.
+                            Some(previous_products) => {
+                                match self.fetch_page_source(&page_url).await? {
+                                    Some(s)
+                                        if count_product_nodes(&s) == 0
+                                            || product_links(&s) == previous_products =>
+                                    {
+                                        info!(
+                                            "Probed page {} has no new products. No more pages left.",
+                                            page_url
+                                        );
+
+                                        continue;
+                                    }
+                                    fetched => fetched,
+                                }
+                            }
+                            None => self.fetch_listing_page(&page_url).await?,
+                        };
+
+                        match fetched {
+                            Some(s) => s,
+                            None if self.report.wrong_currency.is_some() => {
+                                error!("Stopping crawl, the site shows the wrong currency. Page not saved.");
+
+                                break;
+                            }
+                            None => {
+                                error!("Stopping crawl, the site is blocking us. Block page not saved.");
+
+                                self.report.pages_blocked += 1;
 
-                    info!("Found next page at {}", link_url);
+                                break;
+                            }
+                        }
+                    }
+                };
 
-                    page_url = Url::from_str(link_url.as_str()).unwrap();
+                info!("Writing source file to disk: {:?}", source_file_pathbuf);
+
+                write_file_atomically(source_file_pathbuf.as_path(), source.as_ref())
+                    .expect("Failed to write page source to disk.");
+
+                frontier
+                    .mark_visited(&page_url)
+                    .expect("Failed to record visited URL.");
+
+                pages_saved += 1;
+                self.report.pages_saved += 1;
+                self.report.files.push(source_file_name);
+
+                source
+            };
+
+            let product_count = count_product_nodes(&source) as u32;
+
+            products_found += product_count;
+            self.report.products_found += product_count;
+
+            if self.crawl_config.discover {
+                let product_item = Name("li").and(Class("product-item"));
+
+                for url in harvest_links(&source, &page_url, product_item) {
+                    *self.discovered_urls.entry(url.to_string()).or_insert(0) += 1;
                 }
-                Err(_) => {
-                    info!("No more pages left.");
+            }
+
+            let products = product_links(&source);
+
+            self.record_new_products(&products)
+                .expect("Failed to record seen products.");
+
+            if self.crawl_config.new_arrivals
+                && products.iter().any(|p| self.seen_products.contains(p))
+            {
+                info!(
+                    "Page {} has products seen before. No newer products left.",
+                    page_url
+                );
+
+                break;
+            }
+
+            // Checked after the page's products are counted and recorded, so they're not lost.
+            if limits.max_pages == Some(pages_saved) {
+                info!("Reached the crawl limit of {} pages.", pages_saved);
+
+                break;
+            }
+
+            if let Some(max_products) = limits.max_products {
+                if products_found >= max_products {
+                    info!(
+                        "Reached the crawl limit of {} products, with {} found.",
+                        max_products, products_found
+                    );
 
                     break;
                 }
             }
+
+            // The `link` tag is missing on some filtered views, so the pagination widget is
+            // tried next. Failing that, a listing page with products but without a pager may
+            // still be followed by another, so the next page number is probed. A page with a
+            // pager but no next page is the last one.
+            let next_page_url = find_next_page_url(&source, &page_url)
+                .or_else(|| find_pagination_next_url(&source, &page_url))
+                .or_else(|| {
+                    if count_product_nodes(&source) == 0 || has_pager(&source) {
+                        return None;
+                    }
+
+                    let probed_url =
+                        normalize_url(&with_page_number(&page_url, page_number(&page_url) + 1));
+                    info!("Probing for a next page at {}", probed_url);
+                    probed_from.insert(probed_url.to_string(), products.clone());
+
+                    Some(probed_url)
+                });
+
+            match next_page_url {
+                Some(next_page_url) => {
+                    info!("Found next page at {}", next_page_url);
+
+                    if !self.crawl_config.url_filter.allows(&next_page_url) {
+                        info!("Next page is excluded by the URL filters. Stopping.");
+                    } else if !frontier.push(&next_page_url, LISTING_PAGE_PRIORITY) {
+                        info!("Next page was already crawled. Stopping.");
+                    }
+                }
+                None => {
+                    info!("No more pages left.");
+                }
+            }
+        }
+
+        self.frontier = Some(frontier);
+
+        if !self.report.missing_anchors.is_empty() {
+            warn!(
+                "SITE LAYOUT CHANGED? Expected elements were missing from fetched pages \
+                 (element: pages missing it): {:?}. Check the selectors before trusting this crawl.",
+                self.report.missing_anchors
+            );
         }
 
         Ok(())
     }
 }
 
+impl<'a, C: FetchClient> SiteScraper for ClimaticoScraper<'a, C> {
+    fn set_crawl_config(&mut self, crawl_config: CrawlConfig) {
+        ClimaticoScraper::set_crawl_config(self, crawl_config)
+    }
+
+    fn set_visited_urls_file(&mut self, path: &Path) {
+        ClimaticoScraper::set_visited_urls_file(self, path)
+    }
+
+    fn set_seen_products(&mut self, seen_products: HashSet<String>, path: &Path) {
+        ClimaticoScraper::set_seen_products(self, seen_products, path)
+    }
+
+    fn set_navigation_pacer(&mut self, navigation_pacer: NavigationPacer) {
+        ClimaticoScraper::set_navigation_pacer(self, navigation_pacer)
+    }
+
+    fn set_page_cache(&mut self, page_cache: PageCache) {
+        ClimaticoScraper::set_page_cache(self, page_cache)
+    }
+
+    fn start_trace(&mut self, path: &Path) -> std::io::Result<()> {
+        ClimaticoScraper::start_trace(self, path)
+    }
+
+    fn save_page_sources<'b>(
+        &'b mut self,
+        first_page_url: &'b str,
+    ) -> LocalBoxFuture<'b, Result<(), CmdError>> {
+        ClimaticoScraper::save_page_sources(self, first_page_url).boxed_local()
+    }
+
+    fn save_pages<'b>(
+        &'b mut self,
+        page_urls: &'b [Url],
+    ) -> LocalBoxFuture<'b, Result<(), CmdError>> {
+        ClimaticoScraper::save_pages(self, page_urls).boxed_local()
+    }
+
+    fn report(&self) -> &CrawlReport {
+        ClimaticoScraper::report(self)
+    }
+
+    fn discovered_urls(&self) -> &HashMap<String, u32> {
+        ClimaticoScraper::discovered_urls(self)
+    }
+}
+
 //impl ClimaticoScraper {
 //    /// Start scraping an entire product listing, starting at the page specified by the
 //    /// `product_listing_page_url` argument.
@@ -158,3 +1001,336 @@ impl<'a> ClimaticoScraper<'a> {
 //        Ok(0)
 //    }
 //}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::scrapers::fetch::MockFetchClient;
+
+    const FIRST_PAGE_URL: &str = "https://www.climatico.ro/ac";
+    const SECOND_PAGE_URL: &str = "https://www.climatico.ro/ac?p=2";
+
+    /// An empty output folder for the test named `name`.
+    fn output_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("climatico-{}-{}", name, std::process::id()));
+
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    /// Source of a listing page with a pager, showing `products` and linking to
+    /// `next_page_url` with `rel="next"`.
+    fn listing_page(products: &[&str], next_page_url: Option<&str>) -> String {
+        let next_link = next_page_url
+            .map(|u| format!(r#"<link rel="next" href="{}">"#, u))
+            .unwrap_or_default();
+        let items: String = products
+            .iter()
+            .map(|p| {
+                format!(
+                    r#"<li class="product-item"><a class="product-item-link" href="{0}">{0}</a></li>"#,
+                    p
+                )
+            })
+            .collect();
+
+        format!(
+            r#"<html><head>{}</head><body><ol class="product-items">{}</ol><div class="pages"></div></body></html>"#,
+            next_link, items
+        )
+    }
+
+    /// Source of a listing page like [listing_page], but without a pager or a `rel="next"`
+    /// link.
+    fn listing_page_without_pager(products: &[&str]) -> String {
+        listing_page(products, None).replace(r#"<div class="pages"></div>"#, "")
+    }
+
+    /// A client serving a listing of two pages with three products.
+    fn two_page_listing() -> MockFetchClient {
+        let mut client = MockFetchClient::default();
+
+        client.add_page(
+            FIRST_PAGE_URL,
+            &listing_page(&["/ac-1", "/ac-2"], Some(SECOND_PAGE_URL)),
+        );
+        client.add_page(SECOND_PAGE_URL, &listing_page(&["/ac-3"], None));
+
+        client
+    }
+
+    #[tokio::test]
+    async fn follows_rel_next_pagination() {
+        let dir = output_dir("pagination");
+        let mut scraper = ClimaticoScraper::with_client(
+            two_page_listing(),
+            &dir,
+            &dir,
+            BrowserOptions::default(),
+        );
+
+        scraper.save_page_sources(FIRST_PAGE_URL).await.unwrap();
+
+        assert_eq!(
+            scraper.client().visited_urls,
+            vec![FIRST_PAGE_URL, SECOND_PAGE_URL]
+        );
+        assert_eq!(scraper.report().pages_saved, 2);
+        assert_eq!(scraper.report().products_found, 3);
+        assert!(scraper.report().missing_anchors.is_empty());
+    }
+
+    #[tokio::test]
+    async fn names_files_after_page_urls() {
+        let dir = output_dir("file-names");
+        let mut scraper = ClimaticoScraper::with_client(
+            two_page_listing(),
+            &dir,
+            &dir,
+            BrowserOptions::default(),
+        );
+
+        scraper.save_page_sources(FIRST_PAGE_URL).await.unwrap();
+
+        let files = &scraper.report().files;
+
+        assert_eq!(
+            files,
+            &vec![
+                "https__www.climatico.ro__443___ac__.html".to_string(),
+                "https__www.climatico.ro__443___ac__p_2.html".to_string(),
+            ]
+        );
+
+        for file in files {
+            assert!(dir.join(file).exists(), "{} wasn't saved", file);
+        }
+    }
+
+    #[tokio::test]
+    async fn probes_for_next_pages_without_a_pager() {
+        let dir = output_dir("probing");
+        let mut client = MockFetchClient::default();
+
+        client.add_page(
+            FIRST_PAGE_URL,
+            &listing_page_without_pager(&["/ac-1", "/ac-2"]),
+        );
+        client.add_page(SECOND_PAGE_URL, &listing_page_without_pager(&["/ac-3"]));
+        // Magento serves the last page again for page numbers past it.
+        client.add_page(
+            "https://www.climatico.ro/ac?p=3",
+            &listing_page_without_pager(&["/ac-3"]),
+        );
+
+        let mut scraper =
+            ClimaticoScraper::with_client(client, &dir, &dir, BrowserOptions::default());
+
+        scraper.save_page_sources(FIRST_PAGE_URL).await.unwrap();
+
+        assert_eq!(
+            scraper.client().visited_urls,
+            vec![
+                FIRST_PAGE_URL,
+                SECOND_PAGE_URL,
+                "https://www.climatico.ro/ac?p=3"
+            ]
+        );
+        assert_eq!(scraper.report().pages_saved, 2);
+        assert_eq!(scraper.report().products_found, 3);
+    }
+
+    #[tokio::test]
+    async fn resume_reuses_saved_pages() {
+        let dir = output_dir("resume");
+        let mut scraper = ClimaticoScraper::with_client(
+            two_page_listing(),
+            &dir,
+            &dir,
+            BrowserOptions::default(),
+        );
+
+        scraper.save_page_sources(FIRST_PAGE_URL).await.unwrap();
+
+        let mut resumed = ClimaticoScraper::with_client(
+            MockFetchClient::default(),
+            &dir,
+            &dir,
+            BrowserOptions::default(),
+        );
+        resumed.set_crawl_config(CrawlConfig {
+            resume: true,
+            ..CrawlConfig::default()
+        });
+
+        resumed.save_page_sources(FIRST_PAGE_URL).await.unwrap();
+
+        assert!(resumed.client().visited_urls.is_empty());
+        assert_eq!(resumed.report().pages_reused, 2);
+        assert_eq!(resumed.report().pages_saved, 0);
+        assert_eq!(resumed.report().products_found, 3);
+    }
+
+    #[tokio::test]
+    async fn counts_products_of_the_last_page_within_max_pages() {
+        let dir = output_dir("max-pages");
+        let mut scraper = ClimaticoScraper::with_client(
+            two_page_listing(),
+            &dir,
+            &dir,
+            BrowserOptions::default(),
+        );
+        let mut crawl_config = CrawlConfig::default();
+        crawl_config.limits.max_pages = Some(1);
+        scraper.set_crawl_config(crawl_config);
+
+        scraper.save_page_sources(FIRST_PAGE_URL).await.unwrap();
+
+        assert_eq!(scraper.client().visited_urls, vec![FIRST_PAGE_URL]);
+        assert_eq!(scraper.report().products_found, 2);
+    }
+
+    #[tokio::test]
+    async fn new_arrivals_stop_at_the_first_page_with_a_seen_product() {
+        let dir = output_dir("new-arrivals");
+        let third_page_url = "https://www.climatico.ro/ac?p=3";
+        let mut client = MockFetchClient::default();
+
+        client.add_page(
+            FIRST_PAGE_URL,
+            &listing_page(&["/ac-1", "/ac-2"], Some(SECOND_PAGE_URL)),
+        );
+        client.add_page(
+            SECOND_PAGE_URL,
+            &listing_page(&["/ac-3", "/ac-4"], Some(third_page_url)),
+        );
+        client.add_page(third_page_url, &listing_page(&["/ac-5"], None));
+
+        let seen_products_path = dir.join("seen_products.txt");
+        let mut scraper =
+            ClimaticoScraper::with_client(client, &dir, &dir, BrowserOptions::default());
+        scraper.set_crawl_config(CrawlConfig {
+            new_arrivals: true,
+            ..CrawlConfig::default()
+        });
+        scraper.set_seen_products(
+            vec!["/ac-3".to_string()].into_iter().collect(),
+            &seen_products_path,
+        );
+
+        scraper.save_page_sources(FIRST_PAGE_URL).await.unwrap();
+
+        assert_eq!(
+            scraper.client().visited_urls,
+            vec![FIRST_PAGE_URL, SECOND_PAGE_URL]
+        );
+        assert_eq!(
+            std::fs::read_to_string(&seen_products_path).unwrap(),
+            "/ac-1\n/ac-2\n/ac-4\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn counts_pages_missing_a_layout_anchor_once() {
+        let dir = output_dir("anchors");
+        let mut client = MockFetchClient::default();
+
+        client.add_page(FIRST_PAGE_URL, &listing_page(&[], None));
+        client.remove_element("ol.product-items");
+
+        let mut scraper =
+            ClimaticoScraper::with_client(client, &dir, &dir, BrowserOptions::default());
+
+        scraper.save_page_sources(FIRST_PAGE_URL).await.unwrap();
+
+        // The empty page is fetched again, but only counted once.
+        assert_eq!(
+            scraper.client().visited_urls,
+            vec![FIRST_PAGE_URL, FIRST_PAGE_URL]
+        );
+        assert_eq!(scraper.report().missing_anchors["product list"], 1);
+        assert!(scraper.report().degraded);
+    }
+
+    #[tokio::test]
+    async fn saves_given_pages_without_following_their_pagination() {
+        let dir = output_dir("save-pages");
+        let product_page_url = Url::parse("https://www.climatico.ro/ac-1").unwrap();
+        let mut client = two_page_listing();
+
+        client.add_page(product_page_url.as_str(), "<html><h1>AC 1</h1></html>");
+
+        let mut scraper =
+            ClimaticoScraper::with_client(client, &dir, &dir, BrowserOptions::default());
+
+        scraper
+            .save_pages(&[Url::parse(FIRST_PAGE_URL).unwrap(), product_page_url])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            scraper.client().visited_urls,
+            vec![FIRST_PAGE_URL, "https://www.climatico.ro/ac-1"]
+        );
+        assert_eq!(scraper.report().pages_saved, 2);
+        assert_eq!(
+            std::fs::read_to_string(dir.join(&scraper.report().files[1])).unwrap(),
+            "<html><h1>AC 1</h1></html>"
+        );
+    }
+
+    #[tokio::test]
+    async fn stops_at_a_page_still_blocked_after_all_retries() {
+        let dir = output_dir("blocked");
+        let mut client = MockFetchClient::default();
+
+        client.add_page(
+            FIRST_PAGE_URL,
+            "<html><head><title>Too Many Requests</title></head></html>",
+        );
+
+        let mut scraper =
+            ClimaticoScraper::with_client(client, &dir, &dir, BrowserOptions::default());
+        scraper.set_block_detection(BlockDetection {
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(0),
+            ..BlockDetection::default()
+        });
+
+        scraper.save_page_sources(FIRST_PAGE_URL).await.unwrap();
+
+        assert_eq!(scraper.client().visited_urls.len(), 3);
+        assert_eq!(scraper.report().pages_blocked, 1);
+        assert!(scraper.report().files.is_empty());
+    }
+
+    #[tokio::test]
+    async fn counts_a_page_blocked_when_fetched_again_for_products() {
+        let dir = output_dir("blocked-refetch");
+        let mut client = MockFetchClient::default();
+
+        client.queue_page(FIRST_PAGE_URL, &listing_page(&[], None));
+        client.add_page(
+            FIRST_PAGE_URL,
+            "<html><head><title>Too Many Requests</title></head></html>",
+        );
+
+        let mut scraper =
+            ClimaticoScraper::with_client(client, &dir, &dir, BrowserOptions::default());
+        scraper.set_block_detection(BlockDetection {
+            max_retries: 0,
+            ..BlockDetection::default()
+        });
+
+        scraper.save_page_sources(FIRST_PAGE_URL).await.unwrap();
+
+        assert_eq!(scraper.client().visited_urls.len(), 2);
+        assert_eq!(scraper.report().pages_blocked, 1);
+        assert_eq!(scraper.report().pages_without_products, 0);
+        assert!(scraper.report().files.is_empty());
+    }
+}
@@ -0,0 +1,550 @@
+//! Configuration for crawling product listings, shared by all scrapers.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::scrapers::selector_healing::MAX_SUGGESTIONS;
+
+/// Limits that bound a crawl of a product listing, so test runs don't have to walk an entire
+/// category.
+///
+/// Limits apply to each product listing separately. `None` means unlimited.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CrawlLimits {
+    /// Maximum number of pages saved.
+    pub max_pages: Option<u32>,
+    /// Number of products after which no more pages are crawled. The page that reaches it is
+    /// still saved whole, so a few more products may be found.
+    pub max_products: Option<u32>,
+    /// Maximum time spent crawling, checked before fetching each page.
+    pub max_duration: Option<Duration>,
+}
+
+/// Glob patterns that decide which discovered URLs a crawl may follow.
+///
+/// Patterns are matched against the whole URL and `*` matches any sequence of characters,
+/// for example `https://www.climatico.ro/aer-conditionat/*` or `*/checkout/*`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UrlFilter {
+    /// If not empty, a URL must match at least one of these patterns.
+    pub include: Vec<String>,
+    /// A URL must not match any of these patterns.
+    pub exclude: Vec<String>,
+}
+
+impl UrlFilter {
+    /// Whether `url` may be followed.
+    pub fn allows(&self, url: &Url) -> bool {
+        let url = url.as_str();
+
+        (self.include.is_empty() || self.include.iter().any(|p| glob_matches(p, url)))
+            && !self.exclude.iter().any(|p| glob_matches(p, url))
+    }
+}
+
+/// Whether `text` matches the glob `pattern`, in which `*` matches any sequence of characters.
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // `split` always yields at least one part.
+    let first = parts.next().unwrap();
+
+    if !text.starts_with(first) {
+        return false;
+    }
+
+    let mut rest = &text[first.len()..];
+    let mut parts = parts.peekable();
+
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            // The last part must match the end of the text.
+            return rest.ends_with(part);
+        }
+
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+
+    // No `*` in the pattern, so it must match the text exactly.
+    rest.is_empty()
+}
+
+/// What to wait for after navigating to a page and before saving its source, so that content
+/// rendered by JavaScript (like prices loaded by XHR) is captured.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenderWait {
+    /// CSS selector of an element that must be present before the source is saved.
+    pub selector: Option<String>,
+    /// Whether to wait until the page stops loading resources and has no pending jQuery
+    /// requests.
+    pub network_idle: bool,
+    /// Longest time to wait. When it runs out, the source is saved as it is, with a warning.
+    pub timeout: Duration,
+}
+
+impl Default for RenderWait {
+    fn default() -> Self {
+        Self {
+            selector: None,
+            network_idle: false,
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// An element expected on every page of a product listing, like the product list or a price.
+///
+/// Anchors missing from freshly fetched pages usually mean the site's layout changed and the
+/// scraper's selectors need updating.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayoutAnchor {
+    /// Human readable name, used in warnings.
+    pub name: String,
+    /// CSS selector of the element.
+    pub selector: String,
+}
+
+impl LayoutAnchor {
+    pub fn new(name: &str, selector: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            selector: selector.to_string(),
+        }
+    }
+}
+
+/// How much load a crawl may put on a site, applied to every URL on the site's host.
+///
+/// `None` leaves a setting as it is without a profile.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PolitenessProfile {
+    /// Least time between navigating to two pages of the host, by any of the browser sessions
+    /// sharing a [NavigationPacer].
+    pub delay: Option<Duration>,
+    /// Most browser sessions crawling the site at the same time, with [CrawlConfig::parallel].
+    pub max_sessions: Option<usize>,
+    /// User agent the browser sends instead of its own.
+    pub user_agent: Option<String>,
+    /// HTTP proxy the browser connects through, as `host:port`.
+    pub proxy: Option<String>,
+}
+
+/// Spaces out navigations to each host by its [PolitenessProfile::delay].
+///
+/// Clones share the time of the last navigation to each host, so browser sessions crawling a
+/// site in parallel keep to the delay together instead of each on its own.
+#[derive(Debug, Clone, Default)]
+pub struct NavigationPacer {
+    /// When the last navigation to each host happened, or is scheduled to happen.
+    last_navigations: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+impl NavigationPacer {
+    /// Schedules a navigation to `url` at least `delay` after the last one to its host, and
+    /// returns how long to wait for it. Navigations without a delay aren't held back, but still
+    /// count as the last one.
+    pub fn schedule(&self, url: &Url, delay: Option<Duration>) -> Duration {
+        let host = url.host_str().unwrap_or_default().to_string();
+        let now = Instant::now();
+        let mut last_navigations = self
+            .last_navigations
+            .lock()
+            .expect("Failed to lock the navigation times.");
+        let navigate_at = match (delay, last_navigations.get(&host)) {
+            (Some(delay), Some(last_navigation)) => (*last_navigation + delay).max(now),
+            _ => now,
+        };
+
+        last_navigations.insert(host, navigate_at);
+
+        navigate_at - now
+    }
+}
+
+/// Longest politeness delay that isn't likely a mistake, like `2h` meant as `2s`.
+const MAX_POLITENESS_DELAY: Duration = Duration::from_secs(60 * 60);
+
+/// Crawl configuration used by scrapers.
+///
+/// Fields missing from configurations recorded by older versions are read as their defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CrawlConfig {
+    pub limits: CrawlLimits,
+    /// Filters applied to discovered URLs, like the next page of a listing.
+    pub url_filter: UrlFilter,
+    pub render_wait: RenderWait,
+    /// How long fetched pages are reused from the page cache. `None` disables the cache.
+    pub cache_ttl: Option<Duration>,
+    /// Whether to fetch every page again, ignoring the page cache. Fetched pages are still
+    /// cached.
+    pub refresh: bool,
+    /// Whether to collect links to other pages of the site found while crawling, to discover
+    /// categories worth crawling next.
+    pub discover: bool,
+    /// Whether a degraded crawl, see [CrawlReport::degraded], should fail the run.
+    pub strict: bool,
+    /// Whether to resume an interrupted crawl, reusing the pages it already saved instead of
+    /// fetching them again.
+    pub resume: bool,
+    /// Products per listing page to ask for instead of the site's
+    /// [ScraperInfo::product_list_limit](crate::scrapers::registry::ScraperInfo::product_list_limit).
+    pub product_list_limit: Option<String>,
+    /// Whether to crawl listing URLs as they are, for sites that don't allow asking for more
+    /// products per page.
+    pub keep_page_size: bool,
+    /// Whether to crawl a site's listings at the same time, each in its own browser session
+    /// and with its own record of visited URLs, so a failing listing doesn't stop the others.
+    pub parallel: bool,
+    /// Whether to crawl listings sorted by newest first, when the site allows it, and stop at
+    /// the first page showing a product found by an earlier run. A cheap way to find what's new.
+    pub new_arrivals: bool,
+    /// Pages to save instead of crawling the sites' listings, e.g. product pages to fetch
+    /// again. Each is saved as it is, without following its pagination, by the scraper of the
+    /// site on its host.
+    pub urls: Vec<String>,
+    /// Politeness profiles by host, e.g. `www.climatico.ro`.
+    pub politeness: BTreeMap<String, PolitenessProfile>,
+    /// Whether to delete, after a crawl of whole listings, the saved pages it didn't save again
+    /// and the expired pages of the page cache, so the output folder doesn't keep growing.
+    pub prune: bool,
+}
+
+impl CrawlConfig {
+    /// The politeness profile for the host of `url`, or the default profile if it has none.
+    ///
+    /// A profile for `example.com` also applies to `www.example.com`.
+    pub fn politeness_for(&self, url: &Url) -> PolitenessProfile {
+        let host = url.host_str().unwrap_or_default();
+
+        self.politeness
+            .get(host)
+            .or_else(|| self.politeness.get(host.trim_start_matches("www.")))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Whether crawls follow every page of the sites' listings, so the pages they don't save are
+    /// no longer on the sites. Crawls stopped by limits, URL filters or at products seen before
+    /// don't, nor do runs saving only the given [urls](CrawlConfig::urls).
+    pub fn crawls_whole_listings(&self) -> bool {
+        self.limits.max_pages.is_none()
+            && self.limits.max_products.is_none()
+            && self.limits.max_duration.is_none()
+            && self.url_filter.include.is_empty()
+            && self.url_filter.exclude.is_empty()
+            && !self.new_arrivals
+            && self.urls.is_empty()
+    }
+
+    /// Checks the configuration before crawling, instead of failing halfway through a run.
+    ///
+    /// Returns every problem found, each prefixed with the path of the offending field, e.g.
+    /// `limits.max_pages: Must be at least 1.` An empty list means the configuration is valid.
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if self.limits.max_pages == Some(0) {
+            problems.push("limits.max_pages: Must be at least 1.".to_string());
+        }
+
+        if self.limits.max_products == Some(0) {
+            problems.push("limits.max_products: Must be at least 1.".to_string());
+        }
+
+        if self.limits.max_duration == Some(Duration::from_secs(0)) {
+            problems.push("limits.max_duration: Must be longer than 0s.".to_string());
+        }
+
+        for (field, patterns) in &[
+            ("include", &self.url_filter.include),
+            ("exclude", &self.url_filter.exclude),
+        ] {
+            for (i, pattern) in patterns.iter().enumerate() {
+                if pattern.trim().is_empty() {
+                    problems.push(format!("url_filter.{}[{}]: Must not be empty.", field, i));
+                }
+            }
+        }
+
+        if (self.render_wait.selector.is_some() || self.render_wait.network_idle)
+            && self.render_wait.timeout == Duration::from_secs(0)
+        {
+            problems.push("render_wait.timeout: Must be longer than 0s.".to_string());
+        }
+
+        if self.refresh && self.cache_ttl.is_none() {
+            problems.push("refresh: Has no effect without cache_ttl.".to_string());
+        }
+
+        for (i, url) in self.urls.iter().enumerate() {
+            if Url::parse(url).is_err() {
+                problems.push(format!("urls[{}]: {} is not a valid URL.", i, url));
+            }
+        }
+
+        if !self.urls.is_empty() {
+            // Settings for crawling listings, which saving the given pages doesn't do.
+            let crawl_settings = [
+                ("limits.max_pages", self.limits.max_pages.is_some()),
+                ("limits.max_products", self.limits.max_products.is_some()),
+                ("limits.max_duration", self.limits.max_duration.is_some()),
+                ("url_filter.include", !self.url_filter.include.is_empty()),
+                ("url_filter.exclude", !self.url_filter.exclude.is_empty()),
+                ("discover", self.discover),
+                ("resume", self.resume),
+                ("parallel", self.parallel),
+                ("new_arrivals", self.new_arrivals),
+                ("prune", self.prune),
+            ];
+
+            for (field, is_set) in &crawl_settings {
+                if *is_set {
+                    problems.push(format!(
+                        "{}: Can't be used with urls, which are saved without crawling.",
+                        field
+                    ));
+                }
+            }
+        }
+
+        if self.product_list_limit.is_some() && self.keep_page_size {
+            problems.push(
+                "keep_page_size: Can't be used with product_list_limit, which changes the page \
+                 size."
+                    .to_string(),
+            );
+        }
+
+        for (host, profile) in &self.politeness {
+            if Url::parse(&format!("http://{}/", host)).is_err() {
+                problems.push(format!("politeness[{}]: Invalid host.", host));
+            }
+
+            if let Some(delay) = profile.delay {
+                if delay > MAX_POLITENESS_DELAY {
+                    problems.push(format!(
+                        "politeness[{}].delay: {:?} is longer than {:?}.",
+                        host, delay, MAX_POLITENESS_DELAY
+                    ));
+                }
+            }
+
+            if profile.max_sessions == Some(0) {
+                problems.push(format!(
+                    "politeness[{}].max_sessions: Must be at least 1.",
+                    host
+                ));
+            }
+
+            if let Some(proxy) = &profile.proxy {
+                let valid = match proxy.rfind(':') {
+                    Some(i) => i > 0 && proxy[i + 1..].parse::<u16>().is_ok(),
+                    None => false,
+                };
+
+                if !valid {
+                    problems.push(format!(
+                        "politeness[{}].proxy: {} is not written as host:port.",
+                        host, proxy
+                    ));
+                }
+            }
+        }
+
+        problems
+    }
+}
+
+/// What a scraper did while crawling, accumulated over all the listings it crawled.
+///
+/// Fields missing from reports written by older versions are read as their defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CrawlReport {
+    /// Pages fetched and saved to disk.
+    pub pages_saved: u32,
+    /// Pages saved by an interrupted crawl and reused when resuming it.
+    pub pages_reused: u32,
+    /// Pages taken from the page cache instead of being fetched, and saved to disk.
+    pub pages_cached: u32,
+    /// Pages that were still blocked after all retries, and were not saved.
+    pub pages_blocked: u32,
+    /// Pages reused from disk that weren't valid UTF-8 and were transcoded, or had invalid
+    /// bytes replaced. See [crate::scrapers::charset::decode_page_source].
+    pub pages_mis_encoded: u32,
+    /// Products found on the pages saved or reused.
+    pub products_found: u32,
+    /// Names of the page source files saved or reused, in the page sources folder.
+    pub files: Vec<String>,
+    /// Number of fetched pages each [LayoutAnchor] was missing from, by anchor name.
+    pub missing_anchors: BTreeMap<String, u32>,
+    /// Selectors suggested to replace the ones of missing [LayoutAnchor]s, by anchor name. See
+    /// [selector_healing](crate::scrapers::selector_healing).
+    pub selector_suggestions: BTreeMap<String, Vec<String>>,
+    /// Listing pages that still had no products after being fetched a second time.
+    pub pages_without_products: u32,
+    /// Whether the crawl's results are suspicious, e.g. because listing pages had no products,
+    /// which usually means the selectors broke.
+    pub degraded: bool,
+    /// Why the crawl was stopped because the site showed prices in another currency than
+    /// expected, e.g. to a VPN or proxy in another country, like
+    /// [climatico::EXPECTED_CURRENCY](crate::scrapers::climatico::EXPECTED_CURRENCY).
+    pub wrong_currency: Option<String>,
+    /// Listings whose crawl failed, by URL of their first page, with the reason. Only parallel
+    /// crawls carry on past a failing listing.
+    pub failed_listings: BTreeMap<String, String>,
+}
+
+impl CrawlReport {
+    /// Adds `suggestions` for the selector of the anchor named `anchor`, keeping the ones
+    /// already made first and at most [MAX_SUGGESTIONS] in all.
+    pub fn add_selector_suggestions(&mut self, anchor: &str, suggestions: &[String]) {
+        let known = self
+            .selector_suggestions
+            .entry(anchor.to_string())
+            .or_insert_with(Vec::new);
+
+        for suggestion in suggestions {
+            if known.len() < MAX_SUGGESTIONS && !known.contains(suggestion) {
+                known.push(suggestion.clone());
+            }
+        }
+    }
+
+    /// Adds the counts and files of `other`, the report of another listing's crawl, to this
+    /// one.
+    pub fn merge(&mut self, other: &CrawlReport) {
+        self.pages_saved += other.pages_saved;
+        self.pages_reused += other.pages_reused;
+        self.pages_cached += other.pages_cached;
+        self.pages_blocked += other.pages_blocked;
+        self.pages_mis_encoded += other.pages_mis_encoded;
+        self.products_found += other.products_found;
+        self.files.extend(other.files.iter().cloned());
+
+        for (anchor, count) in &other.missing_anchors {
+            *self.missing_anchors.entry(anchor.clone()).or_insert(0) += count;
+        }
+
+        for (anchor, suggestions) in &other.selector_suggestions {
+            self.add_selector_suggestions(anchor, suggestions);
+        }
+
+        self.pages_without_products += other.pages_without_products;
+        self.degraded |= other.degraded;
+
+        if self.wrong_currency.is_none() {
+            self.wrong_currency = other.wrong_currency.clone();
+        }
+
+        self.failed_listings.extend(
+            other
+                .failed_listings
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone())),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_without_star_matches_exactly() {
+        assert!(glob_matches("https://a.ro/x", "https://a.ro/x"));
+        assert!(!glob_matches("https://a.ro/x", "https://a.ro/xy"));
+        assert!(!glob_matches("https://a.ro/x", "https://a.ro/"));
+    }
+
+    #[test]
+    fn glob_star_matches_any_sequence() {
+        assert!(glob_matches(
+            "https://a.ro/ac/*",
+            "https://a.ro/ac/split?p=2"
+        ));
+        assert!(glob_matches("https://a.ro/ac/*", "https://a.ro/ac/"));
+        assert!(glob_matches("*/checkout/*", "https://a.ro/checkout/cart"));
+        assert!(glob_matches("*", ""));
+        assert!(!glob_matches("*/checkout/*", "https://a.ro/cart"));
+    }
+
+    #[test]
+    fn glob_last_part_matches_the_end() {
+        assert!(glob_matches("*.html", "https://a.ro/a.html"));
+        assert!(!glob_matches("*.html", "https://a.ro/a.html?p=2"));
+        assert!(glob_matches("https://*/a*b", "https://a.ro/abab"));
+    }
+
+    #[test]
+    fn url_filter_excludes_take_precedence() {
+        let filter = UrlFilter {
+            include: vec!["https://a.ro/*".to_string()],
+            exclude: vec!["*/checkout/*".to_string()],
+        };
+
+        assert!(filter.allows(&Url::parse("https://a.ro/ac").unwrap()));
+        assert!(!filter.allows(&Url::parse("https://a.ro/checkout/cart").unwrap()));
+        assert!(!filter.allows(&Url::parse("https://b.ro/ac").unwrap()));
+    }
+
+    #[test]
+    fn pacer_spaces_out_navigations_to_a_host_across_clones() {
+        let pacer = NavigationPacer::default();
+        let other_session = pacer.clone();
+        let page = Url::parse("https://www.climatico.ro/ac").unwrap();
+        let delay = Some(Duration::from_secs(10));
+
+        assert_eq!(pacer.schedule(&page, delay), Duration::from_secs(0));
+
+        let first_wait = other_session.schedule(&page, delay);
+        let second_wait = pacer.schedule(&page, delay);
+
+        assert!(first_wait > Duration::from_secs(9) && first_wait <= Duration::from_secs(10));
+        assert!(second_wait > Duration::from_secs(19) && second_wait <= Duration::from_secs(20));
+        assert_eq!(
+            pacer.schedule(&Url::parse("https://example.com/").unwrap(), delay),
+            Duration::from_secs(0)
+        );
+    }
+
+    #[test]
+    fn validate_reports_every_problem() {
+        let mut config = CrawlConfig::default();
+        config.limits.max_pages = Some(0);
+        config.refresh = true;
+        config.urls = vec!["not a url".to_string()];
+
+        let problems = config.validate();
+
+        assert_eq!(problems.len(), 4, "{:?}", problems);
+        assert!(problems[0].starts_with("limits.max_pages: "));
+        assert!(CrawlConfig::default().validate().is_empty());
+    }
+
+    #[test]
+    fn crawls_stopped_early_dont_crawl_whole_listings() {
+        assert!(CrawlConfig::default().crawls_whole_listings());
+
+        let mut limited = CrawlConfig::default();
+        limited.limits.max_duration = Some(Duration::from_secs(60));
+        let mut filtered = CrawlConfig::default();
+        filtered.url_filter.exclude = vec!["*?p=5".to_string()];
+        let new_arrivals = CrawlConfig {
+            new_arrivals: true,
+            ..CrawlConfig::default()
+        };
+
+        for config in &[limited, filtered, new_arrivals] {
+            assert!(!config.crawls_whole_listings(), "{:?}", config);
+        }
+    }
+}
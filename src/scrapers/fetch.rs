@@ -0,0 +1,128 @@
+//! Abstraction over the browser used to fetch pages.
+//!
+//! Scrapers talk to a [FetchClient] instead of a [fantoccini::Client] directly, so their logic
+//! (pagination, file naming, resuming) can run against a [MockFetchClient] serving canned HTML,
+//! without a browser.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use fantoccini::error::CmdError;
+use fantoccini::{Client, Locator};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use serde_json::{json, Value};
+
+/// The browser commands scrapers need to fetch pages.
+pub trait FetchClient: Send {
+    /// Navigates to `url`.
+    fn goto<'a>(&'a mut self, url: &'a str) -> BoxFuture<'a, Result<(), CmdError>>;
+
+    /// Returns the current page's source.
+    fn source(&mut self) -> BoxFuture<'_, Result<String, CmdError>>;
+
+    /// Checks whether an element matching the CSS `selector` is on the current page.
+    fn has_element<'a>(&'a mut self, selector: &'a str) -> BoxFuture<'a, Result<bool, CmdError>>;
+
+    /// Executes `script` in the current page and returns its result.
+    fn execute<'a>(&'a mut self, script: &'a str) -> BoxFuture<'a, Result<Value, CmdError>>;
+}
+
+impl FetchClient for Client {
+    fn goto<'a>(&'a mut self, url: &'a str) -> BoxFuture<'a, Result<(), CmdError>> {
+        Client::goto(self, url).boxed()
+    }
+
+    fn source(&mut self) -> BoxFuture<'_, Result<String, CmdError>> {
+        Client::source(self).boxed()
+    }
+
+    fn has_element<'a>(&'a mut self, selector: &'a str) -> BoxFuture<'a, Result<bool, CmdError>> {
+        async move {
+            match self.find(Locator::Css(selector)).await {
+                Ok(_) => Ok(true),
+                Err(CmdError::NoSuchElement(_)) => Ok(false),
+                Err(e) => Err(e),
+            }
+        }
+        .boxed()
+    }
+
+    fn execute<'a>(&'a mut self, script: &'a str) -> BoxFuture<'a, Result<Value, CmdError>> {
+        Client::execute(self, script, vec![]).boxed()
+    }
+}
+
+/// A [FetchClient] serving canned HTML pages from memory.
+///
+/// Navigating to a URL without a page serves an empty document. Pages
+/// [queued](MockFetchClient::queue_page) for a URL are served first, once each. Element lookups
+/// succeed unless
+/// their selector was [removed](MockFetchClient::remove_element), and scripts report a fully
+/// loaded page, so waiting for pages to render finishes immediately.
+#[derive(Debug, Default)]
+pub struct MockFetchClient {
+    /// Page sources by URL.
+    pages: HashMap<String, String>,
+    /// Page sources by URL, served once each before the one in `pages`.
+    queued_pages: HashMap<String, VecDeque<String>>,
+    /// Selectors that match no element, on any page.
+    missing_elements: HashSet<String>,
+    /// Source of the current page.
+    current_source: Option<String>,
+    /// Every URL navigated to, in order.
+    pub visited_urls: Vec<String>,
+}
+
+impl MockFetchClient {
+    /// Serves `source` when navigating to `url`.
+    pub fn add_page(&mut self, url: &str, source: &str) {
+        self.pages.insert(url.to_string(), source.to_string());
+    }
+
+    /// Serves `source` the next time `url` is navigated to, after the pages queued before it.
+    /// Later navigations serve the page [added](MockFetchClient::add_page) for `url`.
+    pub fn queue_page(&mut self, url: &str, source: &str) {
+        self.queued_pages
+            .entry(url.to_string())
+            .or_default()
+            .push_back(source.to_string());
+    }
+
+    /// Makes lookups of elements matching `selector` fail on every page, e.g. to simulate a
+    /// layout change.
+    pub fn remove_element(&mut self, selector: &str) {
+        self.missing_elements.insert(selector.to_string());
+    }
+}
+
+impl FetchClient for MockFetchClient {
+    fn goto<'a>(&'a mut self, url: &'a str) -> BoxFuture<'a, Result<(), CmdError>> {
+        self.current_source = self
+            .queued_pages
+            .get_mut(url)
+            .and_then(|q| q.pop_front())
+            .or_else(|| self.pages.get(url).cloned());
+        self.visited_urls.push(url.to_string());
+
+        async { Ok(()) }.boxed()
+    }
+
+    fn source(&mut self) -> BoxFuture<'_, Result<String, CmdError>> {
+        let source = self
+            .current_source
+            .clone()
+            .unwrap_or_else(|| "<html><head></head><body></body></html>".to_string());
+
+        async { Ok(source) }.boxed()
+    }
+
+    fn has_element<'a>(&'a mut self, selector: &'a str) -> BoxFuture<'a, Result<bool, CmdError>> {
+        let found = !self.missing_elements.contains(selector);
+
+        async move { Ok(found) }.boxed()
+    }
+
+    fn execute<'a>(&'a mut self, _script: &'a str) -> BoxFuture<'a, Result<Value, CmdError>> {
+        async { Ok(json!(["complete", 0, 0])) }.boxed()
+    }
+}
@@ -0,0 +1,180 @@
+//! A queue of URLs waiting to be crawled.
+//!
+//! The frontier normalizes and deduplicates URLs so the same page is never queued twice in a
+//! run, and keeps a record of visited URLs on disk so an interrupted crawl can be resumed.
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use url::Url;
+
+/// Priority of pages of a product listing.
+pub const LISTING_PAGE_PRIORITY: u32 = 100;
+
+/// Returns `url` in a canonical form, so that equivalent URLs compare equal.
+///
+/// The fragment is removed, query parameters are sorted and an empty query is dropped.
+/// Scheme and host case, as well as default ports, are already normalized by [Url] itself.
+pub fn normalize_url(url: &Url) -> Url {
+    let mut url = url.clone();
+
+    url.set_fragment(None);
+
+    let mut query_pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+
+    if query_pairs.is_empty() {
+        url.set_query(None);
+    } else {
+        query_pairs.sort();
+        url.query_pairs_mut().clear().extend_pairs(query_pairs);
+    }
+
+    url
+}
+
+/// A URL in the frontier's queue.
+#[derive(Debug, PartialEq, Eq)]
+struct QueuedUrl {
+    priority: u32,
+    /// Insertion order, used to keep URLs of equal priority first-in, first-out.
+    sequence: u64,
+    url: Url,
+}
+
+impl Ord for QueuedUrl {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl PartialOrd for QueuedUrl {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A priority queue of URLs to crawl, with deduplication and a persisted visited set.
+#[derive(Debug, Default)]
+pub struct Frontier {
+    queue: BinaryHeap<QueuedUrl>,
+    next_sequence: u64,
+    /// Normalized URLs queued during this run.
+    seen: HashSet<String>,
+    /// Normalized URLs fetched during this or a previous, resumed run.
+    visited: HashSet<String>,
+    /// File the visited URLs are appended to, one per line.
+    visited_file_path: Option<PathBuf>,
+}
+
+impl Frontier {
+    /// Creates a frontier that records visited URLs in the file at `visited_file_path`.
+    ///
+    /// If `resume` is `true`, URLs already recorded in the file are considered visited.
+    /// Otherwise the file is cleared.
+    pub fn with_visited_file(visited_file_path: &Path, resume: bool) -> std::io::Result<Self> {
+        let mut visited = HashSet::new();
+
+        if resume && visited_file_path.exists() {
+            let contents = std::fs::read_to_string(visited_file_path)?;
+
+            visited.extend(contents.lines().filter(|l| !l.is_empty()).map(String::from));
+        } else {
+            std::fs::write(visited_file_path, "")?;
+        }
+
+        Ok(Self {
+            visited,
+            visited_file_path: Some(visited_file_path.to_path_buf()),
+            ..Self::default()
+        })
+    }
+
+    /// Queues `url` with the given `priority`. Higher priorities are popped first.
+    ///
+    /// Returns `false` if the URL was already queued during this run.
+    pub fn push(&mut self, url: &Url, priority: u32) -> bool {
+        let url = normalize_url(url);
+
+        if !self.seen.insert(url.to_string()) {
+            return false;
+        }
+
+        self.queue.push(QueuedUrl {
+            priority,
+            sequence: self.next_sequence,
+            url,
+        });
+        self.next_sequence += 1;
+
+        true
+    }
+
+    /// Takes the next URL to crawl out of the queue.
+    pub fn pop(&mut self) -> Option<Url> {
+        self.queue.pop().map(|q| q.url)
+    }
+
+    /// Whether `url` was already fetched, in this run or in the run being resumed.
+    pub fn is_visited(&self, url: &Url) -> bool {
+        self.visited.contains(normalize_url(url).as_str())
+    }
+
+    /// Records that `url` was fetched.
+    pub fn mark_visited(&mut self, url: &Url) -> std::io::Result<()> {
+        let url = normalize_url(url).to_string();
+
+        if let Some(path) = &self.visited_file_path {
+            let mut file = OpenOptions::new().append(true).create(true).open(path)?;
+
+            writeln!(file, "{}", url)?;
+        }
+
+        self.visited.insert(url);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalized(url: &str) -> String {
+        normalize_url(&Url::parse(url).unwrap()).to_string()
+    }
+
+    #[test]
+    fn normalizing_drops_the_fragment() {
+        assert_eq!(normalized("https://a.ro/ac#reviews"), "https://a.ro/ac");
+    }
+
+    #[test]
+    fn normalizing_sorts_query_parameters() {
+        assert_eq!(
+            normalized("https://a.ro/ac?p=2&product_list_limit=36"),
+            normalized("https://a.ro/ac?product_list_limit=36&p=2")
+        );
+        assert_eq!(
+            normalized("https://a.ro/ac?p=2&a=1"),
+            "https://a.ro/ac?a=1&p=2"
+        );
+    }
+
+    #[test]
+    fn normalizing_drops_an_empty_query() {
+        assert_eq!(normalized("https://a.ro/ac?"), "https://a.ro/ac");
+    }
+
+    #[test]
+    fn normalizing_lowercases_the_host_and_drops_default_ports() {
+        assert_eq!(normalized("HTTPS://A.RO:443/ac"), "https://a.ro/ac");
+    }
+}
@@ -0,0 +1,222 @@
+//! A static HTML page describing a finished run, for reviewing a scrape in a browser without
+//! reading `run.json`.
+//!
+//! The page is written next to the run manifest and compares the run with the previous run of
+//! the same site, if there was one. Saved pages are linked, so they can be opened from it.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::scrapers::crawl::CrawlReport;
+use crate::scrapers::manifest::RunManifest;
+use crate::scrapers::write_file_atomically;
+
+/// Name of the report file, in a site's output folder.
+pub const RUN_REPORT_FILE_NAME: &str = "report.html";
+
+/// Escapes `text` for use in HTML content and attribute values.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Percent-encodes `name` for use as a segment of a relative URL, so characters like `#`, `?`
+/// and `%` in file names are taken literally. Only unreserved characters are left as they are.
+fn encode_path_segment(name: &str) -> String {
+    let mut encoded = String::with_capacity(name.len());
+
+    for byte in name.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => {
+                let _ = write!(encoded, "%{:02X}", byte);
+            }
+        }
+    }
+
+    encoded
+}
+
+/// Counts shown in the summary table, by label.
+fn summary_rows(report: &CrawlReport) -> Vec<(&'static str, u32)> {
+    vec![
+        ("Pages saved", report.pages_saved),
+        ("Pages reused", report.pages_reused),
+        ("Pages from cache", report.pages_cached),
+        ("Pages blocked", report.pages_blocked),
+        ("Pages without products", report.pages_without_products),
+        ("Pages not valid UTF-8", report.pages_mis_encoded),
+        ("Products found", report.products_found),
+    ]
+}
+
+/// Renders the report of the run described by `manifest`, compared with the `previous` run,
+/// if any. Saved pages are linked relative to the site's output folder.
+pub fn render(manifest: &RunManifest, previous: Option<&RunManifest>) -> String {
+    let mut html = String::new();
+    let report = &manifest.report;
+
+    // Writing to a `String` can't fail.
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Run of {site}</title>\n</head>\n<body>\n<h1>Run of {site}</h1>\n\
+         <p>Started at {started} and finished at {finished}, in seconds since the Unix epoch, \
+         by version {version}.</p>\n",
+        site = escape(&manifest.site_id),
+        started = manifest.started_at,
+        finished = manifest.finished_at,
+        version = escape(&manifest.crate_version),
+    );
+
+    if report.degraded {
+        html.push_str(
+            "<p><strong>The crawl was degraded. Check it before using it.</strong></p>\n",
+        );
+    }
+
+    if let Some(reason) = &report.wrong_currency {
+        let _ = writeln!(html, "<p><strong>Stopped: {}</strong></p>", escape(reason));
+    }
+
+    html.push_str("<h2>Summary</h2>\n<table border=\"1\">\n<tr><th></th><th>This run</th>");
+
+    if previous.is_some() {
+        html.push_str("<th>Previous run</th>");
+    }
+
+    html.push_str("</tr>\n");
+
+    let previous_rows = previous.map(|p| summary_rows(&p.report));
+
+    for (i, (label, count)) in summary_rows(report).into_iter().enumerate() {
+        let _ = write!(html, "<tr><td>{}</td><td>{}</td>", label, count);
+
+        if let Some(previous_rows) = &previous_rows {
+            let _ = write!(html, "<td>{}</td>", previous_rows[i].1);
+        }
+
+        html.push_str("</tr>\n");
+    }
+
+    html.push_str("</table>\n<h2>Listings</h2>\n<ul>\n");
+
+    for listing_url in &manifest.listing_urls {
+        let _ = writeln!(
+            html,
+            "<li><a href=\"{url}\">{url}</a></li>",
+            url = escape(listing_url)
+        );
+    }
+
+    html.push_str("</ul>\n");
+
+    if !report.failed_listings.is_empty() {
+        html.push_str("<h2>Failed listings</h2>\n<ul>\n");
+
+        for (listing_url, reason) in &report.failed_listings {
+            let _ = writeln!(html, "<li>{}: {}</li>", escape(listing_url), escape(reason));
+        }
+
+        html.push_str("</ul>\n");
+    }
+
+    if !report.missing_anchors.is_empty() {
+        html.push_str(
+            "<h2>Missing layout anchors</h2>\n<p>The site's layout may have changed.</p>\n\
+             <table border=\"1\">\n<tr><th>Element</th><th>Pages missing it</th>\
+             <th>Similar elements</th></tr>\n",
+        );
+
+        for (anchor, count) in &report.missing_anchors {
+            let suggestions = report
+                .selector_suggestions
+                .get(anchor)
+                .map(|s| s.join(", "))
+                .unwrap_or_default();
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td><code>{}</code></td></tr>",
+                escape(anchor),
+                count,
+                escape(&suggestions)
+            );
+        }
+
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("<h2>Saved pages</h2>\n<ol>\n");
+
+    for file_name in &report.files {
+        let _ = writeln!(
+            html,
+            "<li><a href=\"sources/{href}\">{name}</a></li>",
+            href = escape(&encode_path_segment(file_name)),
+            name = escape(file_name)
+        );
+    }
+
+    html.push_str("</ol>\n</body>\n</html>\n");
+
+    html
+}
+
+/// Writes the report of the run described by `manifest`, compared with the `previous` run, to
+/// `path`.
+pub fn write(
+    path: &Path,
+    manifest: &RunManifest,
+    previous: Option<&RunManifest>,
+) -> std::io::Result<()> {
+    write_file_atomically(path, render(manifest, previous).as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::scrapers::browser::BrowserOptions;
+    use crate::scrapers::crawl::CrawlConfig;
+    use crate::scrapers::manifest::ConfigSnapshot;
+
+    /// The manifest of a run of the site with id `site_id` that saved `files`.
+    fn run(site_id: &str, files: &[&str]) -> RunManifest {
+        let config = ConfigSnapshot {
+            crawl: CrawlConfig::default(),
+            browser: BrowserOptions::default(),
+        };
+        let mut manifest = RunManifest::start(site_id, config, &[]);
+
+        manifest.report.files = files.iter().map(|f| f.to_string()).collect();
+        manifest
+    }
+
+    #[test]
+    fn encodes_file_names_in_links() {
+        assert_eq!(
+            encode_path_segment("https__www.climatico.ro__443___ac__p_2.html"),
+            "https__www.climatico.ro__443___ac__p_2.html"
+        );
+        assert_eq!(
+            encode_path_segment("a#b?c%d é.html"),
+            "a%23b%3Fc%25d%20%C3%A9.html"
+        );
+
+        let html = render(&run("climatico", &["a#b?c%d&e.html"]), None);
+
+        assert!(html.contains(r#"<a href="sources/a%23b%3Fc%25d%26e.html">a#b?c%d&amp;e.html</a>"#));
+    }
+
+    #[test]
+    fn escapes_text_from_the_manifest() {
+        let html = render(&run("<site>", &[]), None);
+
+        assert!(html.contains("<h1>Run of &lt;site&gt;</h1>"));
+        assert!(!html.contains("<site>"));
+    }
+}
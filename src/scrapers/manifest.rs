@@ -0,0 +1,375 @@
+//! The run manifest: a `run.json` file describing a finished scraper run.
+//!
+//! Downstream jobs read the manifest to decide whether to process a run. A site folder without a
+//! manifest holds an unfinished or failed run. Changes to the manifest's format bump
+//! [RUN_MANIFEST_SCHEMA_VERSION].
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::scrapers::browser::BrowserOptions;
+use crate::scrapers::crawl::{CrawlConfig, CrawlReport};
+use crate::scrapers::write_file_atomically;
+
+/// Version of the manifest's format.
+///
+/// * 2 added [RunManifest::checksums].
+/// * 3 added report fields, like [CrawlReport::failed_listings],
+///   [CrawlReport::pages_mis_encoded], [CrawlReport::wrong_currency] and
+///   [CrawlReport::selector_suggestions], and configuration fields. They're read as their
+///   defaults from older manifests.
+pub const RUN_MANIFEST_SCHEMA_VERSION: u32 = 3;
+
+/// Name of the manifest file, in a site's output folder.
+pub const RUN_MANIFEST_FILE_NAME: &str = "run.json";
+
+/// Configuration a run was started with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigSnapshot {
+    pub crawl: CrawlConfig,
+    pub browser: BrowserOptions,
+}
+
+/// Description of a finished scraper run for a single site.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunManifest {
+    /// Version of this format. See [RUN_MANIFEST_SCHEMA_VERSION].
+    pub schema_version: u32,
+    /// Version of this crate that produced the run.
+    pub crate_version: String,
+    /// Id of the scraped site.
+    pub site_id: String,
+    /// When the run started, in seconds since the Unix epoch.
+    pub started_at: u64,
+    /// When the run finished, in seconds since the Unix epoch.
+    pub finished_at: u64,
+    pub config: ConfigSnapshot,
+    /// URLs of the first page of each crawled product listing.
+    pub listing_urls: Vec<String>,
+    /// Page counts and saved files.
+    pub report: CrawlReport,
+    /// SHA-256 checksums of the files in [CrawlReport::files], in hex, by file name. Missing
+    /// from manifests of schema version 1.
+    #[serde(default)]
+    pub checksums: BTreeMap<String, String>,
+}
+
+impl RunManifest {
+    /// Starts the manifest of a run for the site with id `site_id`.
+    ///
+    /// The finish time and the report are filled in by [finish](RunManifest::finish).
+    pub fn start(site_id: &str, config: ConfigSnapshot, listing_urls: &[&str]) -> Self {
+        let now = unix_timestamp();
+
+        Self {
+            schema_version: RUN_MANIFEST_SCHEMA_VERSION,
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            site_id: site_id.to_string(),
+            started_at: now,
+            finished_at: now,
+            config,
+            listing_urls: listing_urls.iter().map(|u| u.to_string()).collect(),
+            report: CrawlReport::default(),
+            checksums: BTreeMap::new(),
+        }
+    }
+
+    /// Records the end of the run with the scraper's `report`.
+    pub fn finish(&mut self, report: CrawlReport) {
+        self.finished_at = unix_timestamp();
+        self.report = report;
+    }
+
+    /// Records the checksums of the files in the report, which are in the `page_sources`
+    /// folder.
+    pub fn record_checksums(&mut self, page_sources: &Path) -> std::io::Result<()> {
+        for file_name in &self.report.files {
+            let checksum = sha256_file(&page_sources.join(file_name))?;
+
+            self.checksums.insert(file_name.clone(), checksum);
+        }
+
+        Ok(())
+    }
+
+    /// Records the checksums again of those of `file_names` this manifest lists, which were saved
+    /// again in the `page_sources` folder, e.g. by a run saving only some pages.
+    pub fn update_checksums(
+        &mut self,
+        page_sources: &Path,
+        file_names: &[String],
+    ) -> std::io::Result<()> {
+        for file_name in file_names {
+            if let Some(checksum) = self.checksums.get_mut(file_name) {
+                *checksum = sha256_file(&page_sources.join(file_name))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks the files in the `page_sources` folder against the recorded checksums.
+    ///
+    /// Returns a description of every file that is missing or whose contents changed.
+    pub fn verify_checksums(&self, page_sources: &Path) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        for (file_name, checksum) in &self.checksums {
+            match sha256_file(&page_sources.join(file_name)) {
+                Ok(c) if &c == checksum => {}
+                Ok(_) => problems.push(format!("{} changed since it was saved.", file_name)),
+                Err(e) => problems.push(format!("{} can't be read: {}", file_name, e)),
+            }
+        }
+
+        problems
+    }
+
+    /// Writes the manifest as pretty JSON to `path`, atomically.
+    pub fn write(&self, path: &Path) -> std::io::Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+
+        write_file_atomically(path, &json)
+    }
+
+    /// Reads the manifest at `path`.
+    pub fn read(path: &Path) -> std::io::Result<Self> {
+        let json = std::fs::read(path)?;
+
+        Ok(serde_json::from_slice(&json)?)
+    }
+}
+
+/// A condition on a run, compared with the previous run, that fails the program when met.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FailCondition {
+    /// More than this percentage fewer products were found than in the previous run.
+    ProductDrop(f32),
+    /// More than this percentage fewer pages were crawled than in the previous run.
+    PageDrop(f32),
+    /// The crawl was degraded, see [CrawlReport::degraded].
+    Degraded,
+    /// Pages were blocked by the site.
+    Blocked,
+}
+
+impl FromStr for FailCondition {
+    type Err = String;
+
+    /// Parses `product-drop:<percent>`, `page-drop:<percent>`, `degraded` or `blocked`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Invalid condition: {}. Expected product-drop:<percent>, page-drop:<percent>, \
+                 degraded or blocked.",
+                s
+            )
+        };
+        let parse_percent = |p: &str| -> Result<f32, String> {
+            p.trim_end_matches('%')
+                .parse()
+                .ok()
+                .filter(|p: &f32| *p >= 0.0)
+                .ok_or_else(invalid)
+        };
+
+        match s.find(':').map(|i| (&s[..i], &s[i + 1..])) {
+            Some(("product-drop", p)) => Ok(FailCondition::ProductDrop(parse_percent(p)?)),
+            Some(("page-drop", p)) => Ok(FailCondition::PageDrop(parse_percent(p)?)),
+            None if s == "degraded" => Ok(FailCondition::Degraded),
+            None if s == "blocked" => Ok(FailCondition::Blocked),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Returns how much smaller `current` is than `previous`, in percent, or `None` if there is
+/// nothing to compare with.
+fn drop_percent(current: u32, previous: u32) -> Option<f32> {
+    if previous == 0 {
+        return None;
+    }
+
+    Some((previous as f32 - current as f32) / previous as f32 * 100.0)
+}
+
+impl RunManifest {
+    /// Checks whether this run meets `condition`, compared with the `previous` run of the same
+    /// site, if any. Returns a description of what was wrong if so.
+    ///
+    /// Conditions comparing with the previous run are never met without one.
+    pub fn check(
+        &self,
+        condition: &FailCondition,
+        previous: Option<&RunManifest>,
+    ) -> Option<String> {
+        let pages = |r: &CrawlReport| r.pages_saved + r.pages_reused + r.pages_cached;
+
+        match condition {
+            FailCondition::ProductDrop(max) => {
+                let previous = previous?;
+                let drop =
+                    drop_percent(self.report.products_found, previous.report.products_found)?;
+
+                (drop > *max).then(|| {
+                    format!(
+                        "Found {} products, {:.1}% fewer than the {} of the previous run.",
+                        self.report.products_found, drop, previous.report.products_found
+                    )
+                })
+            }
+            FailCondition::PageDrop(max) => {
+                let previous = previous?;
+                let drop = drop_percent(pages(&self.report), pages(&previous.report))?;
+
+                (drop > *max).then(|| {
+                    format!(
+                        "Crawled {} pages, {:.1}% fewer than the {} of the previous run.",
+                        pages(&self.report),
+                        drop,
+                        pages(&previous.report)
+                    )
+                })
+            }
+            FailCondition::Degraded => self
+                .report
+                .degraded
+                .then(|| "The crawl was degraded.".to_string()),
+            FailCondition::Blocked => (self.report.pages_blocked > 0)
+                .then(|| format!("{} pages were blocked.", self.report.pages_blocked)),
+        }
+    }
+}
+
+/// Computes the SHA-256 checksum of the file at `path`, in hex.
+pub fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let digest = Sha256::digest(&std::fs::read(path)?);
+
+    Ok(digest.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// The current time in seconds since the Unix epoch.
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The manifest of a run that crawled `pages` pages and found `products` products.
+    fn run(pages: u32, products: u32) -> RunManifest {
+        let config = ConfigSnapshot {
+            crawl: CrawlConfig::default(),
+            browser: BrowserOptions::default(),
+        };
+        let mut manifest = RunManifest::start("climatico", config, &[]);
+
+        manifest.report.pages_saved = pages;
+        manifest.report.products_found = products;
+        manifest
+    }
+
+    #[test]
+    fn parses_fail_conditions() {
+        assert_eq!(
+            "product-drop:20".parse(),
+            Ok(FailCondition::ProductDrop(20.0))
+        );
+        assert_eq!("page-drop:5%".parse(), Ok(FailCondition::PageDrop(5.0)));
+        assert_eq!("degraded".parse(), Ok(FailCondition::Degraded));
+        assert_eq!("blocked".parse(), Ok(FailCondition::Blocked));
+    }
+
+    #[test]
+    fn rejects_invalid_fail_conditions() {
+        for condition in &[
+            "product-drop",
+            "product-drop:-5",
+            "page-drop:x",
+            "degraded:1",
+            "",
+        ] {
+            assert!(
+                condition.parse::<FailCondition>().is_err(),
+                "{} was accepted",
+                condition
+            );
+        }
+    }
+
+    #[test]
+    fn drops_are_met_only_past_their_percentage() {
+        let previous = run(10, 100);
+
+        assert!(run(10, 79)
+            .check(&FailCondition::ProductDrop(20.0), Some(&previous))
+            .is_some());
+        assert!(run(10, 80)
+            .check(&FailCondition::ProductDrop(20.0), Some(&previous))
+            .is_none());
+        assert!(run(8, 100)
+            .check(&FailCondition::PageDrop(10.0), Some(&previous))
+            .is_some());
+    }
+
+    #[test]
+    fn drops_are_never_met_without_a_previous_run_to_compare_with() {
+        assert!(run(0, 0)
+            .check(&FailCondition::ProductDrop(0.0), None)
+            .is_none());
+        assert!(run(0, 0)
+            .check(&FailCondition::PageDrop(0.0), Some(&run(0, 0)))
+            .is_none());
+    }
+
+    #[test]
+    fn checks_degraded_and_blocked_runs() {
+        let mut manifest = run(10, 100);
+
+        assert!(manifest.check(&FailCondition::Degraded, None).is_none());
+        assert!(manifest.check(&FailCondition::Blocked, None).is_none());
+
+        manifest.report.degraded = true;
+        manifest.report.pages_blocked = 1;
+
+        assert!(manifest.check(&FailCondition::Degraded, None).is_some());
+        assert!(manifest.check(&FailCondition::Blocked, None).is_some());
+    }
+
+    #[test]
+    fn updates_only_the_checksums_of_listed_files() {
+        let dir = std::env::temp_dir().join(format!("manifest-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.html"), "before").unwrap();
+
+        let mut manifest = run(1, 0);
+        manifest.report.files = vec!["a.html".to_string()];
+        manifest.record_checksums(&dir).unwrap();
+
+        std::fs::write(dir.join("a.html"), "after").unwrap();
+        std::fs::write(dir.join("b.html"), "new").unwrap();
+
+        assert_eq!(manifest.verify_checksums(&dir).len(), 1);
+
+        manifest
+            .update_checksums(&dir, &["a.html".to_string(), "b.html".to_string()])
+            .unwrap();
+
+        assert!(manifest.verify_checksums(&dir).is_empty());
+        assert_eq!(
+            manifest.checksums.keys().collect::<Vec<_>>(),
+            vec!["a.html"]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
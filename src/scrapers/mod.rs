@@ -1,8 +1,519 @@
 //! A module for different scrapers.
 
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::panic::AssertUnwindSafe;
+use std::path::{Component, Path, PathBuf, Prefix};
+
+use fantoccini::Client;
+use futures::stream::{self, StreamExt};
+use futures::FutureExt;
+use log::{error, info, warn};
+use select::document::Document;
+use select::predicate::{Attr, Class, Name, Predicate};
+use sha2::{Digest, Sha256};
 use url::{Origin, Url};
 
+use crate::scrapers::browser::BrowserOptions;
+use crate::scrapers::cache::PageCache;
+use crate::scrapers::crawl::{CrawlConfig, CrawlReport, NavigationPacer};
+use crate::scrapers::frontier::normalize_url;
+use crate::scrapers::html_report::RUN_REPORT_FILE_NAME;
+use crate::scrapers::manifest::{ConfigSnapshot, RunManifest, RUN_MANIFEST_FILE_NAME};
+use crate::scrapers::registry::{ScraperInfo, ScraperRegistry, SiteScraper};
+
+pub mod anti_bot;
+pub mod browser;
+pub mod cache;
+pub mod charset;
 pub mod climatico;
+pub mod crawl;
+pub mod fetch;
+pub mod frontier;
+pub mod html_report;
+pub mod manifest;
+pub mod registry;
+pub mod selector_healing;
+pub mod stats;
+pub mod trace;
+
+/// Returns the registry of all available scrapers. New scraper modules are registered here.
+pub fn registry() -> ScraperRegistry {
+    ScraperRegistry::new().register(climatico::scraper_info())
+}
+
+/// Name of the page cache folder, in the output root folder. The cache is shared by all sites.
+pub const PAGE_CACHE_DIR_NAME: &str = "cache";
+
+/// Name of the file, in a site's page sources folder, listing the URLs visited by a crawl.
+pub const VISITED_URLS_FILE_NAME: &str = "visited_urls.txt";
+
+/// Output folder paths for a single site, namespaced by the site's id.
+#[derive(Debug)]
+pub struct SiteOutputPaths {
+    /// Folder path for saving web page sources to disk.
+    pub page_sources: PathBuf,
+    /// Folder path for saving scraped product information to disk.
+    pub product_info: PathBuf,
+    /// File path for the trace of WebDriver commands, when tracing is enabled.
+    pub webdriver_trace: PathBuf,
+    /// File path for the manifest of the last finished run.
+    pub run_manifest: PathBuf,
+    /// File path for the HTML report of the last finished run.
+    pub run_report: PathBuf,
+    /// File path for the URLs found by discovery, see [CrawlConfig::discover].
+    pub discovered_urls: PathBuf,
+    /// File path for the product page URLs found by all crawls so far, one per line. See
+    /// [CrawlConfig::new_arrivals].
+    pub seen_products: PathBuf,
+}
+
+impl SiteOutputPaths {
+    /// Output paths for the site with id `site_id`, under the `output_root` folder.
+    ///
+    /// For example, `./out/climatico/sources/` and `./out/climatico/product_info/`.
+    pub fn new(output_root: &Path, site_id: &str) -> Self {
+        let site_root = output_root.join(site_id);
+
+        Self {
+            page_sources: site_root.join("sources"),
+            product_info: site_root.join("product_info"),
+            webdriver_trace: site_root.join("webdriver_trace.jsonl"),
+            run_manifest: site_root.join(RUN_MANIFEST_FILE_NAME),
+            run_report: site_root.join(RUN_REPORT_FILE_NAME),
+            discovered_urls: site_root.join("discovered_urls.tsv"),
+            seen_products: site_root.join("seen_products.txt"),
+        }
+    }
+}
+
+/// Runs the scraper registered for the site with id `site_id` using `crawl_config`, saving its
+/// output under `output_root`. The browser is started with `browser_options`.
+///
+/// Returns the run's manifest, which is also written to disk. A run saving only the pages at
+/// [CrawlConfig::urls] isn't a run of the whole site, so its manifest isn't written. The
+/// previous run's manifest is kept instead, with the checksums of the files saved again
+/// updated.
+///
+/// # Panics
+///
+/// Panics if `site_id` is not in the [registry()].
+pub async fn scrape_site(
+    site_id: &str,
+    output_root: &Path,
+    crawl_config: &CrawlConfig,
+    browser_options: &BrowserOptions,
+) -> Result<RunManifest, fantoccini::error::CmdError> {
+    info!("Scraping site {}.", site_id);
+
+    let registry = registry();
+    let scraper_info = registry
+        .get(site_id)
+        .unwrap_or_else(|| panic!("No scraper registered for site {}.", site_id));
+
+    let output_paths = SiteOutputPaths::new(output_root, site_id);
+    let saves_given_urls = !crawl_config.urls.is_empty();
+
+    // The previous run's manifest and report are removed first, so they can't be mistaken for
+    // this run's if this one doesn't finish.
+    if !saves_given_urls && output_paths.run_manifest.exists() {
+        std::fs::remove_file(&output_paths.run_manifest)
+            .expect("Failed to remove the previous run manifest.");
+    }
+
+    if !saves_given_urls && output_paths.run_report.exists() {
+        std::fs::remove_file(&output_paths.run_report)
+            .expect("Failed to remove the previous run report.");
+    }
+
+    // The browser session is set up for the site's host.
+    let politeness = crawl_config.politeness_for(
+        &Url::parse(scraper_info.base_url).expect("Failed to parse the site's base URL."),
+    );
+    let mut browser_options = browser_options.clone();
+
+    if politeness.user_agent.is_some() {
+        browser_options.user_agent = politeness.user_agent.clone();
+    }
+
+    if politeness.proxy.is_some() {
+        browser_options.proxy = politeness.proxy.clone();
+    }
+
+    let browser_options = &browser_options;
+
+    let config = ConfigSnapshot {
+        crawl: crawl_config.clone(),
+        browser: browser_options.clone(),
+    };
+
+    let mut manifest = RunManifest::start(site_id, config, scraper_info.listing_urls);
+
+    let product_list_limit = crawl_config
+        .product_list_limit
+        .as_deref()
+        .or(scraper_info.product_list_limit)
+        .filter(|_| !crawl_config.keep_page_size);
+    let newest_first_order = if crawl_config.new_arrivals {
+        if scraper_info.newest_first_order.is_none() {
+            warn!(
+                "Site {} can't sort its listings by newest. Stopping at products seen \
+                 before anyway.",
+                site_id
+            );
+        }
+
+        scraper_info.newest_first_order
+    } else {
+        None
+    };
+    let listing_urls: Vec<String> = scraper_info
+        .listing_urls
+        .iter()
+        .map(|listing_url| {
+            let mut listing_url =
+                Url::parse(listing_url).expect("Failed to parse the listing URL into a valid URL.");
+
+            if let Some(limit) = product_list_limit {
+                listing_url = with_product_list_limit(&listing_url, limit);
+            }
+
+            if let Some(order) = newest_first_order {
+                listing_url = with_query_pair(&listing_url, "product_list_order", order);
+                listing_url = with_query_pair(&listing_url, "product_list_dir", "desc");
+            }
+
+            listing_url.to_string()
+        })
+        .collect();
+
+    let setup = ScraperSetup {
+        scraper_info,
+        output_paths: &output_paths,
+        output_root,
+        crawl_config,
+        browser_options,
+        // Read once before crawling, so products found by one listing of this run don't
+        // stop another listing early.
+        seen_products: read_seen_products(&output_paths.seen_products)
+            .expect("Failed to read the seen products file."),
+        // Shared by all browser sessions, so they keep to the politeness delay together.
+        navigation_pacer: NavigationPacer::default(),
+    };
+
+    let (report, discovered_urls) = if saves_given_urls {
+        let host = Url::parse(scraper_info.base_url)
+            .ok()
+            .and_then(|u| u.host_str().map(String::from));
+        let page_urls: Vec<Url> = crawl_config
+            .urls
+            .iter()
+            .filter_map(|u| Url::parse(u).ok())
+            .filter(|u| u.host_str().map(String::from) == host)
+            .collect();
+
+        info!(
+            "Saving {} of the given pages, instead of crawling the listings.",
+            page_urls.len()
+        );
+
+        if page_urls.is_empty() {
+            (CrawlReport::default(), HashMap::new())
+        } else {
+            let mut scraper = new_scraper(
+                browser::connect(browser_options),
+                &setup,
+                &output_paths.webdriver_trace,
+            );
+
+            scraper.save_pages(&page_urls).await?;
+
+            (scraper.report().clone(), scraper.discovered_urls().clone())
+        }
+    } else if crawl_config.parallel {
+        crawl_listings_in_parallel(&listing_urls, &setup, politeness.max_sessions).await
+    } else {
+        let mut scraper = new_scraper(
+            browser::connect(browser_options),
+            &setup,
+            &output_paths.webdriver_trace,
+        );
+
+        for listing_url in &listing_urls {
+            scraper.save_page_sources(listing_url).await?;
+        }
+
+        (scraper.report().clone(), scraper.discovered_urls().clone())
+    };
+
+    manifest.finish(report);
+    manifest
+        .record_checksums(&output_paths.page_sources)
+        .expect("Failed to compute checksums of the page sources.");
+
+    if crawl_config.discover {
+        write_discovered_urls(
+            &output_paths.discovered_urls,
+            &discovered_urls,
+            scraper_info.listing_urls,
+        )
+        .expect("Failed to write the discovered URLs.");
+    }
+
+    if saves_given_urls {
+        if let Ok(mut previous_manifest) = RunManifest::read(&output_paths.run_manifest) {
+            info!(
+                "Updating the checksums of the pages saved again in {:?}",
+                output_paths.run_manifest
+            );
+
+            previous_manifest
+                .update_checksums(&output_paths.page_sources, &manifest.report.files)
+                .expect("Failed to compute checksums of the page sources.");
+            previous_manifest
+                .write(&output_paths.run_manifest)
+                .expect("Failed to write the run manifest.");
+        }
+
+        return Ok(manifest);
+    }
+
+    info!("Writing run manifest to {:?}", output_paths.run_manifest);
+
+    manifest
+        .write(&output_paths.run_manifest)
+        .expect("Failed to write the run manifest.");
+
+    if crawl_config.prune {
+        prune_output(&output_paths, output_root, crawl_config, &manifest.report);
+    }
+
+    Ok(manifest)
+}
+
+/// Deletes the page sources the crawl reported in `report` didn't save, and the expired pages
+/// of the page cache, if the crawl followed every page of the site's listings without problems.
+/// Otherwise the pages it didn't get to may still be on the site, so they're kept.
+fn prune_output(
+    output_paths: &SiteOutputPaths,
+    output_root: &Path,
+    crawl_config: &CrawlConfig,
+    report: &CrawlReport,
+) {
+    if !crawl_config.crawls_whole_listings() || report.degraded || report.pages_blocked > 0 {
+        warn!("Not pruning, the crawl didn't save every page of the site's listings.");
+
+        return;
+    }
+
+    match prune_page_sources(&output_paths.page_sources, &report.files) {
+        Ok(pruned) => info!(
+            "Deleted {} page sources not saved again from {:?}",
+            pruned, output_paths.page_sources
+        ),
+        Err(e) => warn!("Failed to prune {:?}: {}", output_paths.page_sources, e),
+    }
+
+    if let Some(ttl) = crawl_config.cache_ttl {
+        match PageCache::new(&output_root.join(PAGE_CACHE_DIR_NAME), ttl).prune_expired() {
+            Ok(pruned) => info!("Deleted {} expired pages from the page cache.", pruned),
+            Err(e) => warn!("{}", e),
+        }
+    }
+}
+
+/// Deletes the page sources in the `page_sources` folder that aren't among `files`, like pages
+/// a listing no longer has, and the temporary files left by interrupted writes. Other files,
+/// like the visited URLs, are kept.
+///
+/// Returns the number of files deleted.
+fn prune_page_sources(page_sources: &Path, files: &[String]) -> std::io::Result<u32> {
+    let files: HashSet<&str> = files.iter().map(String::as_str).collect();
+    let mut pruned = 0;
+
+    for entry in std::fs::read_dir(page_sources)? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let file_name = file_name.to_string_lossy();
+        let is_page_source = file_name.ends_with(".html") && !files.contains(file_name.as_ref());
+
+        if is_page_source || file_name.ends_with(TMP_FILE_SUFFIX) {
+            std::fs::remove_file(long_path(&entry.path()))?;
+
+            pruned += 1;
+        }
+    }
+
+    Ok(pruned)
+}
+
+/// What every scraper of a site's run is set up with.
+struct ScraperSetup<'a> {
+    /// The site's scraper.
+    scraper_info: &'a ScraperInfo,
+    /// Where the site's output is saved.
+    output_paths: &'a SiteOutputPaths,
+    /// Folder holding the output of all sites, and the page cache.
+    output_root: &'a Path,
+    crawl_config: &'a CrawlConfig,
+    browser_options: &'a BrowserOptions,
+    /// Product page URLs found by earlier runs.
+    seen_products: HashSet<String>,
+    /// Spaces out the navigations of all the run's browser sessions.
+    navigation_pacer: NavigationPacer,
+}
+
+/// Creates the site's scraper fetching pages with `client`, set up with `setup`. Traced
+/// WebDriver commands are recorded in `trace_path`.
+fn new_scraper<'a>(
+    client: Client,
+    setup: &ScraperSetup<'a>,
+    trace_path: &Path,
+) -> Box<dyn SiteScraper + 'a> {
+    let output_paths = setup.output_paths;
+    let mut scraper = (setup.scraper_info.new_scraper)(
+        client,
+        &output_paths.page_sources,
+        &output_paths.product_info,
+        setup.browser_options.clone(),
+    );
+    scraper.set_crawl_config(setup.crawl_config.clone());
+    scraper.set_seen_products(setup.seen_products.clone(), &output_paths.seen_products);
+    scraper.set_navigation_pacer(setup.navigation_pacer.clone());
+
+    if let Some(ttl) = setup.crawl_config.cache_ttl {
+        scraper.set_page_cache(PageCache::new(
+            &setup.output_root.join(PAGE_CACHE_DIR_NAME),
+            ttl,
+        ));
+    }
+
+    if setup.browser_options.trace_commands {
+        scraper
+            .start_trace(trace_path)
+            .expect("Failed to create the WebDriver trace file.");
+    }
+
+    scraper
+}
+
+/// Crawls `listing_urls` at the same time, each with its own browser session, visited URLs
+/// file and trace file, named after [listing_slug]. A listing whose crawl fails or panics is
+/// recorded in [CrawlReport::failed_listings] and marks the crawl as degraded, without
+/// stopping the others.
+///
+/// At most `max_sessions` listings are crawled at once, or all of them if `None`. All sessions
+/// share the navigation pacer of `setup`, so together they keep to the politeness delay.
+///
+/// Returns the merged reports and discovered URLs of all listings.
+async fn crawl_listings_in_parallel(
+    listing_urls: &[String],
+    setup: &ScraperSetup<'_>,
+    max_sessions: Option<usize>,
+) -> (CrawlReport, HashMap<String, u32>) {
+    let output_paths = setup.output_paths;
+
+    std::fs::create_dir_all(&output_paths.page_sources)
+        .expect("Failed to create directory structure.");
+
+    let crawls = listing_urls.iter().map(|listing_url| {
+        let crawl = async move {
+            let slug = listing_slug(listing_url);
+            let client = browser::try_connect(setup.browser_options)
+                .await
+                .map_err(|e| format!("Failed to create new WebDriver session: {}", e))?;
+            let trace_path = output_paths
+                .webdriver_trace
+                .with_file_name(format!("webdriver_trace_{}.jsonl", slug));
+            let mut scraper = new_scraper(client, setup, &trace_path);
+            scraper.set_visited_urls_file(
+                &output_paths
+                    .page_sources
+                    .join(format!("visited_urls_{}.txt", slug)),
+            );
+
+            scraper
+                .save_page_sources(listing_url)
+                .await
+                .map_err(|e| e.to_string())?;
+
+            Ok::<_, String>((scraper.report().clone(), scraper.discovered_urls().clone()))
+        };
+
+        AssertUnwindSafe(crawl).catch_unwind()
+    });
+
+    let mut report = CrawlReport::default();
+    let mut discovered_urls = HashMap::new();
+
+    let results: Vec<_> = stream::iter(crawls)
+        .buffered(max_sessions.unwrap_or(listing_urls.len()).max(1))
+        .collect()
+        .await;
+
+    for (listing_url, result) in listing_urls.iter().zip(results) {
+        let failure = match result {
+            Ok(Ok((listing_report, listing_discovered_urls))) => {
+                report.merge(&listing_report);
+
+                for (url, count) in listing_discovered_urls {
+                    *discovered_urls.entry(url).or_insert(0) += count;
+                }
+
+                continue;
+            }
+            Ok(Err(e)) => e,
+            Err(panic) => match panic.downcast_ref::<&str>() {
+                Some(message) => message.to_string(),
+                None => panic
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .unwrap_or_else(|| "Unknown panic.".to_string()),
+            },
+        };
+
+        error!("Crawling listing {} failed: {}", listing_url, failure);
+
+        report.failed_listings.insert(listing_url.clone(), failure);
+        report.degraded = true;
+    }
+
+    (report, discovered_urls)
+}
+
+/// Names the files of a listing crawled in parallel after the path of its `listing_url`, e.g.
+/// `aer-conditionat_comercial` for `https://www.climatico.ro/aer-conditionat/comercial`.
+pub fn listing_slug(listing_url: &str) -> String {
+    let path = Url::parse(listing_url)
+        .map(|u| u.path().trim_matches('/').replace("/", "_"))
+        .unwrap_or_default();
+
+    sanitize_file_name(&path)
+}
+
+/// Writes the URLs found by discovery to `path`, one per line after the number of pages linking
+/// to it, most linked first. URLs inside one of the crawled `listing_urls` are left out.
+fn write_discovered_urls(
+    path: &Path,
+    discovered_urls: &HashMap<String, u32>,
+    listing_urls: &[&str],
+) -> std::io::Result<()> {
+    let mut candidates: Vec<(&String, &u32)> = discovered_urls
+        .iter()
+        .filter(|(url, _)| !listing_urls.iter().any(|l| url.starts_with(l)))
+        .collect();
+
+    candidates.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+
+    info!("Writing {} discovered URLs to {:?}", candidates.len(), path);
+
+    let contents: String = candidates
+        .iter()
+        .map(|(url, count)| format!("{}\t{}\n", count, url))
+        .collect();
+
+    write_file_atomically(path, contents.as_bytes())
+}
 
 /// Turns a URL to a HTML page into a valid file name.
 pub fn url_to_html_file_name(url: &Url) -> Result<String, String> {
@@ -26,10 +537,281 @@ pub fn url_to_html_file_name(url: &Url) -> Result<String, String> {
         Some(q) => q.replace("=", "_"),
     };
 
-    Ok(format!(
+    Ok(sanitize_file_name(&format!(
         "{}__{}__{}__{}__{}.html",
         scheme, host, port, path, query_params
-    ))
+    )))
+}
+
+/// Longest file name, in bytes, that the common file systems accept.
+const MAX_FILE_NAME_LEN: usize = 255;
+
+/// Suffix of the temporary file [write_file_atomically] writes before renaming it.
+const TMP_FILE_SUFFIX: &str = ".tmp";
+
+/// Longest path, in characters, that Windows accepts without the `\\?\` prefix.
+const WINDOWS_MAX_PATH: usize = 260;
+
+/// Names of devices that can't be used as file names on Windows, whatever their extension.
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Makes `file_name` safe to use as a file name on this platform.
+///
+/// On Windows, characters it doesn't allow are replaced with `_`, as are trailing dots and
+/// spaces, and reserved device names like `CON` get a `_` prefix. Other platforms keep these,
+/// so file names saved by earlier runs still match.
+///
+/// On all platforms, a name too long for [MAX_FILE_NAME_LEN] bytes, once [TMP_FILE_SUFFIX] is
+/// added while [writing it atomically](write_file_atomically), is cut short. It keeps its
+/// extension and ends with a hash of the full name, so different long names stay different.
+pub fn sanitize_file_name(file_name: &str) -> String {
+    let max_len = MAX_FILE_NAME_LEN - TMP_FILE_SUFFIX.len();
+    let mut name = file_name.to_string();
+
+    if cfg!(windows) {
+        name = name
+            .chars()
+            .map(|c| match c {
+                '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+                c if c.is_control() => '_',
+                c => c,
+            })
+            .collect();
+
+        let trimmed_len = name.trim_end_matches(|c| c == '.' || c == ' ').len();
+        let trailing = name.len() - trimmed_len;
+        name.truncate(trimmed_len);
+        name.push_str(&"_".repeat(trailing));
+
+        let stem = name.split('.').next().unwrap_or_default().to_uppercase();
+        if WINDOWS_RESERVED_NAMES.contains(&stem.as_str()) {
+            name.insert(0, '_');
+        }
+    }
+
+    if name.len() <= max_len {
+        return name;
+    }
+
+    let digest = Sha256::digest(file_name.as_bytes());
+    let hash: String = digest
+        .iter()
+        .take(8)
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let extension = match name.rfind('.') {
+        Some(i) if name.len() - i <= 16 => name[i..].to_string(),
+        _ => String::new(),
+    };
+
+    let mut end = max_len - extension.len() - hash.len() - 2;
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    format!("{}__{}{}", &name[..end], hash, extension)
+}
+
+/// Returns `path` in a form Windows can open even when it's longer than [WINDOWS_MAX_PATH].
+///
+/// Long paths are made absolute and get the `\\?\` prefix, which lifts the limit but also
+/// turns off the resolution of `.` and `..`, so those are resolved here. Short paths, and all
+/// paths on other platforms, are returned unchanged.
+pub fn long_path(path: &Path) -> PathBuf {
+    if !cfg!(windows) || path.as_os_str().len() < WINDOWS_MAX_PATH {
+        return path.to_path_buf();
+    }
+
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        match std::env::current_dir() {
+            Ok(dir) => dir.join(path),
+            Err(_) => return path.to_path_buf(),
+        }
+    };
+
+    let mut resolved = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::Prefix(prefix) => match prefix.kind() {
+                Prefix::Disk(_) => {
+                    resolved.push(format!("\\\\?\\{}", prefix.as_os_str().to_string_lossy()))
+                }
+                Prefix::UNC(server, share) => resolved.push(format!(
+                    "\\\\?\\UNC\\{}\\{}",
+                    server.to_string_lossy(),
+                    share.to_string_lossy()
+                )),
+                // Already verbatim, or a device path.
+                _ => return absolute,
+            },
+            Component::RootDir => resolved.push("\\"),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::Normal(part) => resolved.push(part),
+        }
+    }
+
+    resolved
+}
+
+/// Finds the URL of the next page of a product listing in the page's `source`.
+///
+/// Expects a `link` tag in the HTML header that points to the next URL.
+/// For example `<link rel="next" href="https://...?p=2">`.
+/// Relative URLs are resolved against `page_url`.
+pub fn find_next_page_url(source: &str, page_url: &Url) -> Option<Url> {
+    let document = Document::from(source);
+    let href = document
+        .find(Name("link").and(Attr("rel", "next")))
+        .filter_map(|n| n.attr("href"))
+        .next()?;
+
+    page_url.join(href).ok()
+}
+
+/// Finds the URL of the next page of a product listing in the pagination widget of the page's
+/// `source`, for pages without a `link` tag, see [find_next_page_url].
+///
+/// Expects a Magento pager, e.g. `<div class="pages">`, with a "next" button or a link to the
+/// page numbered one more than `page_url`, see [page_number]. Relative URLs are resolved against
+/// `page_url`.
+pub fn find_pagination_next_url(source: &str, page_url: &Url) -> Option<Url> {
+    let document = Document::from(source);
+    let pager = document.find(Class("pages")).next()?;
+
+    let next_button = pager
+        .find(Class("pages-item-next").descendant(Name("a")))
+        .chain(pager.find(Name("a").and(Class("next"))))
+        .filter_map(|n| page_url.join(n.attr("href")?).ok())
+        .next();
+
+    if next_button.is_some() {
+        return next_button;
+    }
+
+    let next_number = page_number(page_url) + 1;
+
+    pager
+        .find(Name("a"))
+        .filter_map(|n| page_url.join(n.attr("href")?).ok())
+        .find(|url| page_number(url) == next_number)
+}
+
+/// Whether the page's `source` has a Magento pager, e.g. `<div class="pages">`. A page with a
+/// pager but no next page, see [find_pagination_next_url], is the last page of its listing.
+pub fn has_pager(source: &str) -> bool {
+    Document::from(source).find(Class("pages")).next().is_some()
+}
+
+/// Number of the listing page at `page_url`, from its `p` query parameter, or 1 without one.
+pub fn page_number(page_url: &Url) -> u32 {
+    page_url
+        .query_pairs()
+        .find(|(k, _)| k == "p")
+        .and_then(|(_, v)| v.parse().ok())
+        .unwrap_or(1)
+}
+
+/// Returns `page_url` with its `p` query parameter set to `number`, for probing listing pages
+/// that no link points to.
+pub fn with_page_number(page_url: &Url, number: u32) -> Url {
+    let mut url = page_url.clone();
+    let query_pairs: Vec<(String, String)> = page_url
+        .query_pairs()
+        .filter(|(k, _)| k != "p")
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+
+    url.query_pairs_mut()
+        .clear()
+        .extend_pairs(query_pairs)
+        .append_pair("p", &number.to_string());
+
+    url
+}
+
+/// Returns `listing_url` asking for `limit` products per page, e.g. `36` or `all`, with
+/// Magento's `product_list_limit` query parameter, so fewer pages are fetched.
+pub fn with_product_list_limit(listing_url: &Url, limit: &str) -> Url {
+    with_query_pair(listing_url, "product_list_limit", limit)
+}
+
+/// Returns `url` with its query parameter `key` set to `value`, replacing any it had.
+pub fn with_query_pair(url: &Url, key: &str, value: &str) -> Url {
+    let mut new_url = url.clone();
+    let query_pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(k, _)| k != key)
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+
+    new_url
+        .query_pairs_mut()
+        .clear()
+        .extend_pairs(query_pairs)
+        .append_pair(key, value);
+
+    new_url
+}
+
+/// Reads the product page URLs in the seen products file at `path`, see
+/// [SiteOutputPaths::seen_products]. There are none if the file doesn't exist yet.
+pub fn read_seen_products(path: &Path) -> std::io::Result<HashSet<String>> {
+    if !path.exists() {
+        return Ok(HashSet::new());
+    }
+
+    Ok(std::fs::read_to_string(path)?
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// Finds the links in a page's `source` that point to other pages of the same site, for
+/// discovering what else the site has. Links inside elements matching `skip_within`, like
+/// product cards, are skipped.
+///
+/// Relative URLs are resolved against `page_url`. The returned URLs are normalized and unique.
+pub fn harvest_links<P: Predicate>(source: &str, page_url: &Url, skip_within: P) -> Vec<Url> {
+    let document = Document::from(source);
+    let mut seen = HashSet::new();
+
+    document
+        .find(Name("a"))
+        .filter(|n| {
+            !std::iter::successors(n.parent(), |p| p.parent()).any(|p| skip_within.matches(&p))
+        })
+        .filter_map(|n| page_url.join(n.attr("href")?).ok())
+        .filter(|url| ["http", "https"].contains(&url.scheme()) && url.host() == page_url.host())
+        .map(|url| normalize_url(&url))
+        .filter(|url| seen.insert(url.to_string()))
+        .collect()
+}
+
+/// Writes `contents` to `path` so that `path` either holds the complete contents or is left
+/// untouched.
+///
+/// The data is first written and synced to a sibling file named with [TMP_FILE_SUFFIX], which
+/// is then renamed over `path`. A crash halfway through leaves behind only the temporary file,
+/// never a truncated `path`.
+pub fn write_file_atomically(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut tmp_file_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_file_name.push(TMP_FILE_SUFFIX);
+    let tmp_path = path.with_file_name(tmp_file_name);
+
+    let mut tmp_file = std::fs::File::create(&tmp_path)?;
+    tmp_file.write_all(contents)?;
+    tmp_file.sync_all()?;
+
+    std::fs::rename(&tmp_path, path)
 }
 
 pub mod data {
@@ -37,18 +819,95 @@ pub mod data {
     //!
     //! All data structures are serializable using the [serde] crate.
 
+    use std::collections::BTreeMap;
+    use std::convert::TryFrom;
+    use std::str::FromStr;
+
     use serde::{Deserialize, Serialize};
 
     /// Currency sign.
-    #[derive(Debug, Serialize, Deserialize)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
     pub enum Currency {
         RON,
         USD,
         EUR,
     }
 
+    impl FromStr for Currency {
+        type Err = String;
+
+        /// Parses a currency code or a sign as shown on Romanian sites, e.g. `lei`, `€` or `USD`.
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s.trim().to_lowercase().as_str() {
+                "ron" | "lei" | "leu" => Ok(Currency::RON),
+                "usd" | "$" => Ok(Currency::USD),
+                "eur" | "euro" | "€" => Ok(Currency::EUR),
+                _ => Err(format!("Unknown currency: {}", s)),
+            }
+        }
+    }
+
+    /// Parses the currency sign written next to a price, e.g. `lei` in `2.499,99 lei` or `€` in
+    /// `€ 99.99`.
+    pub fn currency_of_price(price: &str) -> Result<Currency, String> {
+        let sign = price
+            .trim()
+            .trim_matches(|c: char| c.is_ascii_digit() || ",. ".contains(c));
+
+        Currency::from_str(sign)
+    }
+
+    /// Parses a price written in the Romanian format, e.g. `1.234,56`, ignoring any currency
+    /// sign and whitespace around the number.
+    ///
+    /// Dots are thousands separators and the comma is the decimal separator. Without a comma,
+    /// a single dot followed by one or two digits is the decimal separator instead, as in
+    /// `€ 99.99`.
+    pub fn parse_price(price: &str) -> Result<f32, String> {
+        let digits_and_separators: String = price
+            .chars()
+            .filter(|c| c.is_ascii_digit() || *c == ',' || *c == '.')
+            .collect();
+        let dot_is_decimal = !digits_and_separators.contains(',')
+            && digits_and_separators.matches('.').count() == 1
+            && digits_and_separators
+                .rsplit('.')
+                .next()
+                .map_or(false, |decimals| (1..=2).contains(&decimals.len()));
+        let number: String = if dot_is_decimal {
+            digits_and_separators
+        } else {
+            digits_and_separators
+                .chars()
+                .filter(|c| *c != '.')
+                .map(|c| if c == ',' { '.' } else { c })
+                .collect()
+        };
+
+        number
+            .parse()
+            .map_err(|_| format!("Invalid price: {:?}", price))
+    }
+
+    /// Trims whitespace around `value`, or returns `None` if nothing is left.
+    fn non_empty(value: &str) -> Option<&str> {
+        Some(value.trim()).filter(|v| !v.is_empty())
+    }
+
+    /// Parses a yes/no answer as shown in product specifications, e.g. `Da` or `Nu`.
+    pub fn parse_yes_no(answer: &str) -> Result<bool, String> {
+        match answer.trim().to_lowercase().as_str() {
+            "da" | "yes" => Ok(true),
+            "nu" | "no" | "" => Ok(false),
+            _ => Err(format!("Invalid yes/no answer: {:?}", answer)),
+        }
+    }
+
     /// AC (air conditioning) product.
-    #[derive(Debug, Serialize, Deserialize)]
+    ///
+    /// It's not `Eq` or `Hash` because of the floating point price. Deduplicate by the
+    /// [RawACProduct] it was converted from, or by product code, instead.
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
     pub struct ACProduct<'a> {
         /// Product name.
         pub name: &'a str,
@@ -60,11 +919,13 @@ pub mod data {
 
         /// URL for the product page on the reseller's website.
         pub reseller_product_page_url: &'a str,
-        /// URL for the official manufacturer's product page.
-        pub manufacturer_product_page_url: &'a str,
+        /// URL for the official manufacturer's product page, if the reseller links to it.
+        #[serde(borrow, skip_serializing_if = "Option::is_none")]
+        pub manufacturer_product_page_url: Option<&'a str>,
 
-        /// File path for main image used to list the product.
-        pub listing_image_path: &'a str,
+        /// File path for main image used to list the product, once it's downloaded.
+        #[serde(borrow, skip_serializing_if = "Option::is_none")]
+        pub listing_image_path: Option<&'a str>,
         /// URL for the main image used to list the product.
         pub listing_image_url: &'a str,
 
@@ -79,8 +940,11 @@ pub mod data {
         /// fits a certain mounting place.
         pub internal_unit_length: &'a str,
 
-        pub heating_noise_level: &'a str,
-        pub cooling_noise_level: &'a str,
+        /// Noise levels, if the specifications list them.
+        #[serde(borrow, skip_serializing_if = "Option::is_none")]
+        pub heating_noise_level: Option<&'a str>,
+        #[serde(borrow, skip_serializing_if = "Option::is_none")]
+        pub cooling_noise_level: Option<&'a str>,
 
         pub heating_energy_class: &'a str,
         pub cooling_energy_class: &'a str,
@@ -101,4 +965,444 @@ pub mod data {
         /// * `["Residential", "AC", "Console"]`.
         pub category_drill_down: Vec<String>,
     }
+
+    /// AC (air conditioning) product exactly as scraped, before any parsing or normalization.
+    ///
+    /// Every value is the text found on the page, so nothing is lost if it doesn't parse.
+    /// Convert it into an [ACProduct] with [TryFrom].
+    #[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    pub struct RawACProduct {
+        pub name: String,
+        pub manufacturer: String,
+        pub product_code: String,
+        pub reseller_product_page_url: String,
+        pub manufacturer_product_page_url: String,
+        pub listing_image_path: String,
+        pub listing_image_url: String,
+        /// Price as shown, e.g. `2.499,99 lei`.
+        pub price: String,
+        /// Currency as shown, e.g. `lei`. Empty if it's part of `price`.
+        pub currency: String,
+        /// Answer as shown, e.g. `Da` or `Nu`.
+        pub has_wifi_connection: String,
+        pub mains_voltage: String,
+        pub internal_unit_length: String,
+        pub heating_noise_level: String,
+        pub cooling_noise_level: String,
+        pub heating_energy_class: String,
+        pub cooling_energy_class: String,
+        pub heating_btu_capacity: String,
+        pub cooling_btu_capacity: String,
+        pub category_drill_down: Vec<String>,
+    }
+
+    impl<'a> TryFrom<&'a RawACProduct> for ACProduct<'a> {
+        type Error = String;
+
+        /// Parses the price, currency and yes/no answers, and trims whitespace around all
+        /// other values.
+        ///
+        /// Fails if the name, the product code or the price is missing, or if a value can't be
+        /// parsed.
+        fn try_from(raw: &'a RawACProduct) -> Result<Self, Self::Error> {
+            let name = raw.name.trim();
+            let product_code = raw.product_code.trim();
+
+            if name.is_empty() {
+                return Err("Product has no name.".to_string());
+            }
+
+            if product_code.is_empty() {
+                return Err(format!("Product {:?} has no product code.", name));
+            }
+
+            let currency = if raw.currency.trim().is_empty() {
+                currency_of_price(&raw.price)?
+            } else {
+                Currency::from_str(&raw.currency)?
+            };
+
+            Ok(ACProduct {
+                name,
+                manufacturer: raw.manufacturer.trim(),
+                product_code,
+                reseller_product_page_url: raw.reseller_product_page_url.trim(),
+                manufacturer_product_page_url: non_empty(&raw.manufacturer_product_page_url),
+                listing_image_path: non_empty(&raw.listing_image_path),
+                listing_image_url: raw.listing_image_url.trim(),
+                price: parse_price(&raw.price)?,
+                currency,
+                has_wifi_connection: parse_yes_no(&raw.has_wifi_connection)?,
+                mains_voltage: raw.mains_voltage.trim(),
+                internal_unit_length: raw.internal_unit_length.trim(),
+                heating_noise_level: non_empty(&raw.heating_noise_level),
+                cooling_noise_level: non_empty(&raw.cooling_noise_level),
+                heating_energy_class: raw.heating_energy_class.trim(),
+                cooling_energy_class: raw.cooling_energy_class.trim(),
+                heating_btu_capacity: raw.heating_btu_capacity.trim(),
+                cooling_btu_capacity: raw.cooling_btu_capacity.trim(),
+                category_drill_down: raw
+                    .category_drill_down
+                    .iter()
+                    .map(|c| c.trim().to_string())
+                    .filter(|c| !c.is_empty())
+                    .collect(),
+            })
+        }
+    }
+
+    /// Product category, deciding which attributes a [Product] has.
+    #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    pub enum ProductCategory {
+        /// AC (air conditioning) unit, with the attributes in [ac_attributes].
+        AirConditioner,
+        Dehumidifier,
+        HeatPump,
+        /// Any other category, by name.
+        Other(String),
+    }
+
+    /// Names of the [Product] attributes of an AC unit.
+    pub mod ac_attributes {
+        pub const HAS_WIFI_CONNECTION: &str = "has_wifi_connection";
+        pub const MAINS_VOLTAGE: &str = "mains_voltage";
+        pub const INTERNAL_UNIT_LENGTH: &str = "internal_unit_length";
+        pub const HEATING_NOISE_LEVEL: &str = "heating_noise_level";
+        pub const COOLING_NOISE_LEVEL: &str = "cooling_noise_level";
+        pub const HEATING_ENERGY_CLASS: &str = "heating_energy_class";
+        pub const COOLING_ENERGY_CLASS: &str = "cooling_energy_class";
+        pub const HEATING_BTU_CAPACITY: &str = "heating_btu_capacity";
+        pub const COOLING_BTU_CAPACITY: &str = "cooling_btu_capacity";
+    }
+
+    /// A product of any category.
+    ///
+    /// Fields common to all categories are typed, while category-specific ones are kept as
+    /// named attributes. Typed views, like [ACProduct], are converted from and into it.
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct Product {
+        pub name: String,
+        pub manufacturer: String,
+        /// Uniquely identifying product code.
+        pub product_code: String,
+        pub reseller_product_page_url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub manufacturer_product_page_url: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub listing_image_path: Option<String>,
+        pub listing_image_url: String,
+        pub price: f32,
+        pub currency: Currency,
+        pub category: ProductCategory,
+        /// A drill down of product categories and subcategories, as on the reseller's website.
+        /// See [ACProduct::category_drill_down].
+        pub category_drill_down: Vec<String>,
+        /// Category-specific attributes by name, e.g. the ones in [ac_attributes].
+        pub attributes: BTreeMap<String, String>,
+    }
+
+    impl Product {
+        /// Value of the attribute named `name`, or an empty string if the product doesn't have
+        /// it.
+        pub fn attribute(&self, name: &str) -> &str {
+            self.attributes.get(name).map(String::as_str).unwrap_or("")
+        }
+
+        /// Value of the attribute named `name`, or `None` if the product doesn't have it.
+        pub fn optional_attribute(&self, name: &str) -> Option<&str> {
+            self.attributes.get(name).map(String::as_str)
+        }
+    }
+
+    impl<'a> From<&ACProduct<'a>> for Product {
+        fn from(ac: &ACProduct<'a>) -> Self {
+            use ac_attributes::*;
+
+            let attributes = [
+                (
+                    HAS_WIFI_CONNECTION,
+                    Some(if ac.has_wifi_connection { "Da" } else { "Nu" }),
+                ),
+                (MAINS_VOLTAGE, Some(ac.mains_voltage)),
+                (INTERNAL_UNIT_LENGTH, Some(ac.internal_unit_length)),
+                (HEATING_NOISE_LEVEL, ac.heating_noise_level),
+                (COOLING_NOISE_LEVEL, ac.cooling_noise_level),
+                (HEATING_ENERGY_CLASS, Some(ac.heating_energy_class)),
+                (COOLING_ENERGY_CLASS, Some(ac.cooling_energy_class)),
+                (HEATING_BTU_CAPACITY, Some(ac.heating_btu_capacity)),
+                (COOLING_BTU_CAPACITY, Some(ac.cooling_btu_capacity)),
+            ]
+            .iter()
+            // Missing optional attributes are left out.
+            .filter_map(|(k, v)| Some((k.to_string(), (*v)?.to_string())))
+            .collect();
+
+            Product {
+                name: ac.name.to_string(),
+                manufacturer: ac.manufacturer.to_string(),
+                product_code: ac.product_code.to_string(),
+                reseller_product_page_url: ac.reseller_product_page_url.to_string(),
+                manufacturer_product_page_url: ac.manufacturer_product_page_url.map(String::from),
+                listing_image_path: ac.listing_image_path.map(String::from),
+                listing_image_url: ac.listing_image_url.to_string(),
+                price: ac.price,
+                currency: ac.currency,
+                category: ProductCategory::AirConditioner,
+                category_drill_down: ac.category_drill_down.clone(),
+                attributes,
+            }
+        }
+    }
+
+    impl<'a> TryFrom<&'a Product> for ACProduct<'a> {
+        type Error = String;
+
+        /// Views a generic product as an AC unit. Fails if it's from another category.
+        fn try_from(product: &'a Product) -> Result<Self, Self::Error> {
+            use ac_attributes::*;
+
+            if product.category != ProductCategory::AirConditioner {
+                return Err(format!(
+                    "Product {:?} is not an AC unit but {:?}.",
+                    product.name, product.category
+                ));
+            }
+
+            Ok(ACProduct {
+                name: &product.name,
+                manufacturer: &product.manufacturer,
+                product_code: &product.product_code,
+                reseller_product_page_url: &product.reseller_product_page_url,
+                manufacturer_product_page_url: product.manufacturer_product_page_url.as_deref(),
+                listing_image_path: product.listing_image_path.as_deref(),
+                listing_image_url: &product.listing_image_url,
+                price: product.price,
+                currency: product.currency,
+                has_wifi_connection: parse_yes_no(product.attribute(HAS_WIFI_CONNECTION))?,
+                mains_voltage: product.attribute(MAINS_VOLTAGE),
+                internal_unit_length: product.attribute(INTERNAL_UNIT_LENGTH),
+                heating_noise_level: product.optional_attribute(HEATING_NOISE_LEVEL),
+                cooling_noise_level: product.optional_attribute(COOLING_NOISE_LEVEL),
+                heating_energy_class: product.attribute(HEATING_ENERGY_CLASS),
+                cooling_energy_class: product.attribute(COOLING_ENERGY_CLASS),
+                heating_btu_capacity: product.attribute(HEATING_BTU_CAPACITY),
+                cooling_btu_capacity: product.attribute(COOLING_BTU_CAPACITY),
+                category_drill_down: product.category_drill_down.clone(),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_files_after_their_url() {
+        let url = Url::parse("https://www.climatico.ro/aer-conditionat/comercial?p=2").unwrap();
+
+        assert_eq!(
+            url_to_html_file_name(&url).unwrap(),
+            "https__www.climatico.ro__443___aer-conditionat_comercial__p_2.html"
+        );
+    }
+
+    #[test]
+    fn keeps_short_file_names() {
+        assert_eq!(sanitize_file_name("page.html"), "page.html");
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn keeps_characters_other_platforms_allow() {
+        assert_eq!(sanitize_file_name("a:b?c.html"), "a:b?c.html");
+        assert_eq!(sanitize_file_name("CON.html"), "CON.html");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn replaces_characters_windows_forbids() {
+        assert_eq!(sanitize_file_name("a:b?c.html"), "a_b_c.html");
+        assert_eq!(sanitize_file_name("CON.html"), "_CON.html");
+        assert_eq!(sanitize_file_name("page. "), "page__");
+    }
+
+    #[test]
+    fn shortens_long_file_names_keeping_them_distinct() {
+        let a = sanitize_file_name(&format!("{}a.html", "x".repeat(300)));
+        let b = sanitize_file_name(&format!("{}b.html", "x".repeat(300)));
+
+        assert!(a.ends_with(".html"));
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn shortened_file_names_can_be_written_atomically() {
+        let dir = std::env::temp_dir().join(format!("long-file-names-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // The second name is cut on a char boundary.
+        for long_name in &[
+            format!("{}.html", "x".repeat(300)),
+            format!("{}.html", "ă".repeat(200)),
+        ] {
+            let path = dir.join(sanitize_file_name(long_name));
+
+            write_file_atomically(&path, b"<html></html>").unwrap();
+
+            assert_eq!(std::fs::read(&path).unwrap(), b"<html></html>");
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn prunes_page_sources_not_saved_again() {
+        let dir = std::env::temp_dir().join(format!("prune-sources-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        for file_name in &[
+            "kept.html",
+            "gone.html",
+            "gone.html.tmp",
+            VISITED_URLS_FILE_NAME,
+        ] {
+            std::fs::write(dir.join(file_name), "").unwrap();
+        }
+
+        assert_eq!(
+            prune_page_sources(&dir, &["kept.html".to_string()]).unwrap(),
+            2
+        );
+
+        let mut left: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        left.sort();
+
+        assert_eq!(left, vec!["kept.html", VISITED_URLS_FILE_NAME]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn finds_the_next_page_in_the_pager() {
+        let page_url = Url::parse("https://www.climatico.ro/ac?p=2").unwrap();
+        let next_button = r#"<div class="pages"><ul>
+            <li class="pages-item-next"><a href="/ac?p=3">Next</a></li>
+        </ul></div>"#;
+        let numbered_links = r#"<div class="pages">
+            <a href="/ac?p=1">1</a><a href="/ac?p=3">3</a><a href="/ac?p=4">4</a>
+        </div>"#;
+
+        assert_eq!(
+            find_pagination_next_url(next_button, &page_url).map(String::from),
+            Some("https://www.climatico.ro/ac?p=3".to_string())
+        );
+        assert_eq!(
+            find_pagination_next_url(numbered_links, &page_url).map(String::from),
+            Some("https://www.climatico.ro/ac?p=3".to_string())
+        );
+    }
+
+    #[test]
+    fn finds_no_next_page_on_the_last_page_or_without_a_pager() {
+        let page_url = Url::parse("https://www.climatico.ro/ac?p=2").unwrap();
+        let last_page = r#"<div class="pages"><a href="/ac?p=1">1</a></div>"#;
+
+        assert_eq!(find_pagination_next_url(last_page, &page_url), None);
+        assert!(has_pager(last_page));
+        assert_eq!(find_pagination_next_url("<ol></ol>", &page_url), None);
+        assert!(!has_pager("<ol></ol>"));
+    }
+
+    #[test]
+    fn sets_page_numbers_keeping_other_query_parameters() {
+        let first_page = Url::parse("https://www.climatico.ro/ac?product_list_limit=36").unwrap();
+        let second_page = with_page_number(&first_page, 2);
+
+        assert_eq!(page_number(&first_page), 1);
+        assert_eq!(page_number(&second_page), 2);
+        assert_eq!(
+            with_page_number(&second_page, 3).as_str(),
+            "https://www.climatico.ro/ac?product_list_limit=36&p=3"
+        );
+    }
+
+    /// The price in `price` in cents, to compare prices without float equality.
+    fn price_in_cents(price: &str) -> Result<i64, String> {
+        data::parse_price(price).map(|p| (f64::from(p) * 100.0).round() as i64)
+    }
+
+    #[test]
+    fn parses_romanian_and_dot_decimal_prices() {
+        assert_eq!(price_in_cents("2.499,99 lei"), Ok(249_999));
+        assert_eq!(price_in_cents("1.234 lei"), Ok(123_400));
+        assert_eq!(price_in_cents("€ 99.99"), Ok(9_999));
+        assert!(price_in_cents("lei").is_err());
+    }
+
+    fn ac_product() -> data::ACProduct<'static> {
+        data::ACProduct {
+            name: "Aer conditionat Daikin Perfera 12000 BTU",
+            manufacturer: "Daikin",
+            product_code: "FTXM35R",
+            reseller_product_page_url: "https://www.climatico.ro/daikin-perfera",
+            manufacturer_product_page_url: None,
+            listing_image_path: None,
+            listing_image_url: "https://www.climatico.ro/media/daikin-perfera.jpg",
+            price: 2499.99,
+            currency: data::Currency::RON,
+            has_wifi_connection: true,
+            mains_voltage: "230 V",
+            internal_unit_length: "798 mm",
+            heating_noise_level: None,
+            cooling_noise_level: Some("19 dB"),
+            heating_energy_class: "A++",
+            cooling_energy_class: "A+++",
+            heating_btu_capacity: "12000",
+            cooling_btu_capacity: "12000",
+            category_drill_down: vec!["Rezidential".to_string(), "Aer conditionat".to_string()],
+        }
+    }
+
+    #[test]
+    fn ac_products_convert_to_products_and_back() {
+        use std::convert::TryFrom;
+
+        let ac = ac_product();
+        let product = data::Product::from(&ac);
+
+        assert_eq!(product.category, data::ProductCategory::AirConditioner);
+        assert_eq!(
+            product.attribute(data::ac_attributes::HAS_WIFI_CONNECTION),
+            "Da"
+        );
+        assert_eq!(data::ACProduct::try_from(&product), Ok(ac));
+    }
+
+    #[test]
+    fn products_leave_out_missing_optional_attributes() {
+        let product = data::Product::from(&ac_product());
+
+        assert_eq!(
+            product.optional_attribute(data::ac_attributes::HEATING_NOISE_LEVEL),
+            None
+        );
+        assert_eq!(
+            product.optional_attribute(data::ac_attributes::COOLING_NOISE_LEVEL),
+            Some("19 dB")
+        );
+    }
+
+    #[test]
+    fn only_ac_units_are_viewed_as_ac_products() {
+        use std::convert::TryFrom;
+
+        let mut product = data::Product::from(&ac_product());
+        product.category = data::ProductCategory::Dehumidifier;
+
+        assert!(data::ACProduct::try_from(&product).is_err());
+    }
 }
@@ -0,0 +1,132 @@
+//! The registry of available scrapers.
+//!
+//! Every scraper module describes itself with a [ScraperInfo], which is registered in
+//! [crate::scrapers::registry()]. The command line uses the registry to validate and list sites,
+//! and [crate::scrapers::scrape_site] to create the scraper of a site with
+//! [ScraperInfo::new_scraper].
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use fantoccini::error::CmdError;
+use fantoccini::Client;
+use futures::future::LocalBoxFuture;
+use url::Url;
+
+use crate::scrapers::browser::BrowserOptions;
+use crate::scrapers::cache::PageCache;
+use crate::scrapers::crawl::{CrawlConfig, CrawlReport, NavigationPacer};
+use crate::scrapers::data::ProductCategory;
+
+/// What [crate::scrapers::scrape_site] needs of a site's scraper to set it up, crawl the site's
+/// listings or save given pages, and report on it.
+pub trait SiteScraper {
+    /// Replaces the default crawl configuration.
+    fn set_crawl_config(&mut self, crawl_config: CrawlConfig);
+
+    /// Records the URLs visited by crawls in the file at `path`, instead of the scraper's
+    /// default file.
+    fn set_visited_urls_file(&mut self, path: &Path);
+
+    /// Treats `seen_products` as found by earlier runs and records the product page URLs found
+    /// that aren't among them in the file at `path`, one per line.
+    fn set_seen_products(&mut self, seen_products: HashSet<String>, path: &Path);
+
+    /// Spaces out navigations with `navigation_pacer`, shared with the site's other scrapers.
+    fn set_navigation_pacer(&mut self, navigation_pacer: NavigationPacer);
+
+    /// Reuses pages from `page_cache` while they're fresh, and caches fetched pages in it.
+    fn set_page_cache(&mut self, page_cache: PageCache);
+
+    /// Records the WebDriver commands issued in the trace file at `path`.
+    fn start_trace(&mut self, path: &Path) -> std::io::Result<()>;
+
+    /// Crawls the listing starting at `first_page_url`, saving the source of its pages.
+    fn save_page_sources<'a>(
+        &'a mut self,
+        first_page_url: &'a str,
+    ) -> LocalBoxFuture<'a, Result<(), CmdError>>;
+
+    /// Saves the source of the page at each of `page_urls` as it is, without crawling.
+    fn save_pages<'a>(
+        &'a mut self,
+        page_urls: &'a [Url],
+    ) -> LocalBoxFuture<'a, Result<(), CmdError>>;
+
+    /// What was crawled so far.
+    fn report(&self) -> &CrawlReport;
+
+    /// Links found while crawling, with the number of pages linking to each.
+    fn discovered_urls(&self) -> &HashMap<String, u32>;
+}
+
+/// Creates a site's scraper fetching pages with a client started with the browser options, and
+/// saving page sources and product information in the first and second folder.
+pub type NewScraper =
+    for<'a> fn(Client, &'a Path, &'a Path, BrowserOptions) -> Box<dyn SiteScraper + 'a>;
+
+/// Description of a scraper for a single site.
+#[derive(Debug, Clone)]
+pub struct ScraperInfo {
+    /// Id of the site, used to select it on the command line and to namespace its output.
+    pub site_id: &'static str,
+    /// Human readable name of the site.
+    pub name: &'static str,
+    /// Home page of the site.
+    pub base_url: &'static str,
+    /// Categories of the products the scraper finds.
+    pub categories: Vec<ProductCategory>,
+    /// URLs of the first page of each product listing crawled by default.
+    pub listing_urls: &'static [&'static str],
+    /// Most products per listing page the site allows, e.g. `36` or `all`, asked for with the
+    /// `product_list_limit` query parameter. `None` if the site doesn't support it.
+    pub product_list_limit: Option<&'static str>,
+    /// Value of the `product_list_order` query parameter sorting listings by date added, e.g.
+    /// `created_at`, asked for in descending order. `None` if the site can't sort by newest.
+    pub newest_first_order: Option<&'static str>,
+    /// Counts the products in the source of a listing page.
+    pub count_products: fn(&str) -> usize,
+    /// Creates the site's scraper.
+    pub new_scraper: NewScraper,
+}
+
+/// All available scrapers, in the order they were registered.
+#[derive(Debug, Default)]
+pub struct ScraperRegistry {
+    scrapers: Vec<ScraperInfo>,
+}
+
+impl ScraperRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the scraper described by `info`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a scraper for the same site is already registered.
+    pub fn register(mut self, info: ScraperInfo) -> Self {
+        if self.get(info.site_id).is_some() {
+            panic!("A scraper for site {} is already registered.", info.site_id);
+        }
+
+        self.scrapers.push(info);
+        self
+    }
+
+    /// Returns the scraper for the site with id `site_id`, if there is one.
+    pub fn get(&self, site_id: &str) -> Option<&ScraperInfo> {
+        self.scrapers.iter().find(|s| s.site_id == site_id)
+    }
+
+    /// Ids of all the registered sites.
+    pub fn site_ids(&self) -> Vec<&'static str> {
+        self.scrapers.iter().map(|s| s.site_id).collect()
+    }
+
+    /// All the registered scrapers.
+    pub fn scrapers(&self) -> &[ScraperInfo] {
+        &self.scrapers
+    }
+}
@@ -0,0 +1,144 @@
+//! Suggestions for replacing selectors that stopped matching.
+//!
+//! When a site's layout changes, its elements are usually renamed rather than removed, e.g.
+//! `product-items` becomes `products-list`. The class names in a broken selector are what it
+//! last matched, so elements of the page whose classes share words with them are suggested as
+//! replacements. The suggestions are only hints and still need checking by hand.
+
+use std::collections::{BTreeMap, HashSet};
+
+use select::document::Document;
+use select::predicate::Attr;
+
+/// Most suggestions made for a single selector.
+pub const MAX_SUGGESTIONS: usize = 3;
+
+/// Words shorter than this, like `ol` or `a`, match too many classes to be useful.
+const MIN_WORD_LEN: usize = 3;
+
+/// The tag and class names of the element matched by `selector`, taken from its last simple
+/// selector, e.g. `ol` and `["product-items"]` for `main ol.product-items`.
+fn target_element(selector: &str) -> (Option<&str>, Vec<&str>) {
+    let last = selector
+        .split(|c: char| c.is_whitespace() || c == '>' || c == '+' || c == '~')
+        .rfind(|s| !s.is_empty())
+        .unwrap_or("");
+    // Ids, attributes and pseudo-classes are ignored.
+    let last = last
+        .split(|c: char| c == '#' || c == '[' || c == ':')
+        .next()
+        .unwrap_or("");
+    let mut parts = last.split('.');
+    let tag = parts.next().filter(|t| !t.is_empty() && *t != "*");
+
+    (tag, parts.filter(|c| !c.is_empty()).collect())
+}
+
+/// Words in a class name, e.g. `product` and `items` in `product-items`.
+fn words(class: &str) -> impl Iterator<Item = String> + '_ {
+    class
+        .split(|c: char| c == '-' || c == '_')
+        .filter(|w| w.len() >= MIN_WORD_LEN)
+        .map(|w| w.to_lowercase())
+}
+
+/// Suggests up to [MAX_SUGGESTIONS] selectors for elements of the page `source` that look like
+/// the one `selector` used to match, best first.
+///
+/// Elements are ranked by how many of their classes share a word with the classes in
+/// `selector`, then by whether they have the same tag, then by how often they occur. Returns
+/// nothing if `selector` has no classes to go by.
+pub fn suggest_selectors(source: &str, selector: &str) -> Vec<String> {
+    let (tag, classes) = target_element(selector);
+    let expected_words: HashSet<String> = classes.iter().flat_map(|c| words(c)).collect();
+
+    if expected_words.is_empty() {
+        return Vec::new();
+    }
+
+    // Score and number of occurrences, by suggested selector.
+    let mut candidates: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+
+    for node in Document::from(source).find(Attr("class", ())) {
+        let node_tag = match node.name() {
+            Some(n) => n,
+            None => continue,
+        };
+        let similar_classes: Vec<&str> = node
+            .attr("class")
+            .unwrap_or("")
+            .split_whitespace()
+            .filter(|c| words(c).any(|w| expected_words.contains(&w)))
+            .collect();
+
+        if similar_classes.is_empty() {
+            continue;
+        }
+
+        let score = similar_classes.len() * 2 + (tag == Some(node_tag)) as usize;
+        let suggestion = format!("{}.{}", node_tag, similar_classes.join("."));
+
+        if suggestion == selector {
+            continue;
+        }
+
+        let candidate = candidates.entry(suggestion).or_insert((score, 0));
+        candidate.1 += 1;
+    }
+
+    let mut candidates: Vec<(String, (usize, usize))> = candidates.into_iter().collect();
+    candidates.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+    candidates
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(s, _)| s)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn targets_the_last_simple_selector() {
+        assert_eq!(
+            target_element("main > ol.product-items.grid:first-child"),
+            (Some("ol"), vec!["product-items", "grid"])
+        );
+        assert_eq!(target_element(".price-box .price"), (None, vec!["price"]));
+    }
+
+    #[test]
+    fn suggests_renamed_elements_with_the_same_tag_first() {
+        let source = r#"<main>
+            <div class="product-info"></div>
+            <div class="product-info"></div>
+            <ol class="product-grid"></ol>
+            <ul class="menu-items"></ul>
+            <p class="footer"></p>
+        </main>"#;
+
+        assert_eq!(
+            suggest_selectors(source, "ol.product-items"),
+            vec!["ol.product-grid", "div.product-info", "ul.menu-items"]
+        );
+    }
+
+    #[test]
+    fn suggests_nothing_without_classes_to_go_by() {
+        let source = r#"<ol class="product-items"></ol>"#;
+
+        assert!(suggest_selectors(source, "ol").is_empty());
+        assert!(suggest_selectors(source, "#products").is_empty());
+        // Words shorter than MIN_WORD_LEN are ignored.
+        assert!(suggest_selectors(source, "ol.pr").is_empty());
+    }
+
+    #[test]
+    fn does_not_suggest_the_selector_itself() {
+        let source = r#"<ol class="product-items"></ol>"#;
+
+        assert!(suggest_selectors(source, "ol.product-items").is_empty());
+    }
+}
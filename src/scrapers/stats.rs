@@ -0,0 +1,354 @@
+//! Statistics over the page sources saved for a site, to check whether a crawl is complete.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::UNIX_EPOCH;
+
+use url::Url;
+
+use crate::scrapers::charset::read_page_source;
+use crate::scrapers::manifest::{unix_timestamp, RunManifest};
+use crate::scrapers::registry::ScraperInfo;
+use crate::scrapers::{long_path, url_to_html_file_name, SiteOutputPaths};
+
+/// Statistics of the saved pages of a single product listing.
+#[derive(Debug, Default)]
+pub struct ListingStats {
+    /// Page numbers saved, in order.
+    pub pages: Vec<u32>,
+}
+
+impl ListingStats {
+    /// Page numbers missing between the first page and the last saved one.
+    pub fn missing_pages(&self) -> Vec<u32> {
+        let last = self.pages.last().copied().unwrap_or(0);
+
+        (1..last).filter(|p| !self.pages.contains(p)).collect()
+    }
+}
+
+/// Statistics of the page sources saved for a site.
+#[derive(Debug, Default)]
+pub struct SiteStats {
+    pub site_id: String,
+    /// Saved pages by listing, named after the listing's URL path.
+    pub listings: BTreeMap<String, ListingStats>,
+    /// Number of saved pages.
+    pub page_count: u32,
+    /// Number of pages listed by the last run's manifest that are gone from disk.
+    pub missing_files: u32,
+    /// Total size of the saved pages, in bytes.
+    pub total_size: u64,
+    /// When the oldest and the newest page were saved, in seconds since the Unix epoch.
+    pub saved_between: Option<(u64, u64)>,
+    /// Number of pages by the number of products on them.
+    pub products_per_page: BTreeMap<usize, u32>,
+    /// Number of pages that weren't valid UTF-8 and were decoded lossily.
+    pub mis_encoded_pages: u32,
+    /// The last finished run, if its manifest is there.
+    pub last_run: Option<RunManifest>,
+}
+
+/// Finds which of `listing_urls` the page source file named `file_name`, see
+/// [url_to_html_file_name], is a page of. Returns the path of the listing's URL and the page
+/// number, which is 1 without a `p` query parameter, or `None` if the file is of no listing.
+///
+/// File names are matched against the names of the listings' pages, as file names can't be
+/// turned back into URLs, e.g. both `/` and `_` in paths are saved as `_`. The listing with the
+/// longest matching name wins, so a listing nested in another gets its own pages.
+fn listing_and_page(file_name: &str, listing_urls: &[String]) -> Option<(String, u32)> {
+    let (listing_url, query) = listing_urls
+        .iter()
+        .filter_map(|listing_url| {
+            let mut listing_url = Url::parse(listing_url).ok()?;
+            listing_url.set_query(None);
+            listing_url.set_fragment(None);
+
+            // The name of a page without a query ends with `__.html`.
+            let page_name = url_to_html_file_name(&listing_url).ok()?;
+            let prefix = page_name.strip_suffix(".html")?;
+            let query = file_name.strip_prefix(prefix)?.strip_suffix(".html")?;
+
+            Some((listing_url, query))
+        })
+        .max_by_key(|(listing_url, _)| listing_url.path().len())?;
+
+    let page = query
+        .split('&')
+        .find_map(|p| p.strip_prefix("p_")?.parse().ok())
+        .unwrap_or(1);
+
+    Some((listing_url.path().to_string(), page))
+}
+
+/// Collects statistics over the page sources saved for the site described by `scraper_info`.
+///
+/// The pages are the ones listed by the last run's manifest, when there is one, and the ones
+/// in the page sources folder otherwise. They're grouped by the listings the run crawled, or
+/// by the site's listings without a manifest.
+pub fn site_stats(
+    scraper_info: &ScraperInfo,
+    output_paths: &SiteOutputPaths,
+) -> std::io::Result<SiteStats> {
+    let mut stats = SiteStats {
+        site_id: scraper_info.site_id.to_string(),
+        ..SiteStats::default()
+    };
+
+    if output_paths.run_manifest.exists() {
+        stats.last_run = Some(RunManifest::read(&output_paths.run_manifest)?);
+    }
+
+    let (file_names, listing_urls) = match &stats.last_run {
+        Some(run) => (run.report.files.clone(), run.listing_urls.clone()),
+        None if output_paths.page_sources.exists() => {
+            let mut file_names = Vec::new();
+
+            for entry in std::fs::read_dir(&output_paths.page_sources)? {
+                let file_name = entry?.file_name().to_string_lossy().into_owned();
+
+                if file_name.ends_with(".html") {
+                    file_names.push(file_name);
+                }
+            }
+
+            let listing_urls = scraper_info
+                .listing_urls
+                .iter()
+                .map(|u| u.to_string())
+                .collect();
+
+            (file_names, listing_urls)
+        }
+        None => return Ok(stats),
+    };
+
+    for file_name in file_names {
+        let path = long_path(&output_paths.page_sources.join(&file_name));
+
+        if !path.exists() {
+            stats.missing_files += 1;
+
+            continue;
+        }
+
+        let metadata = std::fs::metadata(&path)?;
+        let saved_at = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let decoded = read_page_source(&path)?;
+        let source = decoded.text;
+
+        if decoded.mis_encoded {
+            stats.mis_encoded_pages += 1;
+        }
+
+        stats.page_count += 1;
+        stats.total_size += metadata.len();
+        stats.saved_between = Some(match stats.saved_between {
+            Some((oldest, newest)) => (oldest.min(saved_at), newest.max(saved_at)),
+            None => (saved_at, saved_at),
+        });
+        *stats
+            .products_per_page
+            .entry((scraper_info.count_products)(&source))
+            .or_insert(0) += 1;
+
+        if let Some((listing, page)) = listing_and_page(&file_name, &listing_urls) {
+            stats.listings.entry(listing).or_default().pages.push(page);
+        }
+    }
+
+    for listing in stats.listings.values_mut() {
+        listing.pages.sort_unstable();
+    }
+
+    Ok(stats)
+}
+
+/// Formats a number of seconds ago as a rough age, e.g. `3h` or `2d`.
+fn format_age(timestamp: u64) -> String {
+    let seconds = unix_timestamp().saturating_sub(timestamp);
+
+    match seconds {
+        s if s < 60 => format!("{}s", s),
+        s if s < 60 * 60 => format!("{}m", s / 60),
+        s if s < 24 * 60 * 60 => format!("{}h", s / (60 * 60)),
+        s => format!("{}d", s / (24 * 60 * 60)),
+    }
+}
+
+impl fmt::Display for SiteStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Site {}:", self.site_id)?;
+
+        match &self.last_run {
+            Some(run) => writeln!(
+                f,
+                "  Last run finished {} ago, saving {} pages and reusing {}{}.",
+                format_age(run.finished_at),
+                run.report.pages_saved,
+                run.report.pages_reused,
+                if run.report.degraded {
+                    " (degraded)"
+                } else {
+                    ""
+                }
+            )?,
+            None => writeln!(f, "  No finished run.")?,
+        }
+
+        if self.missing_files > 0 {
+            writeln!(
+                f,
+                "  {} pages saved by the last run are missing.",
+                self.missing_files
+            )?;
+        }
+
+        if self.page_count == 0 {
+            return writeln!(f, "  No saved pages.");
+        }
+
+        writeln!(
+            f,
+            "  {} pages, {:.1} KiB on average.",
+            self.page_count,
+            self.total_size as f64 / f64::from(self.page_count) / 1024.0
+        )?;
+
+        if let Some((oldest, newest)) = self.saved_between {
+            writeln!(
+                f,
+                "  Saved between {} and {} ago.",
+                format_age(oldest),
+                format_age(newest)
+            )?;
+        }
+
+        let distribution: Vec<String> = self
+            .products_per_page
+            .iter()
+            .rev()
+            .map(|(products, pages)| format!("{} products on {} pages", products, pages))
+            .collect();
+
+        writeln!(f, "  {}.", distribution.join(", "))?;
+
+        if self.mis_encoded_pages > 0 {
+            writeln!(
+                f,
+                "  {} pages are not valid UTF-8 and were decoded lossily.",
+                self.mis_encoded_pages
+            )?;
+        }
+
+        for (listing, listing_stats) in &self.listings {
+            let missing_pages = listing_stats.missing_pages();
+
+            write!(f, "  {}: {} pages", listing, listing_stats.pages.len())?;
+
+            if missing_pages.is_empty() {
+                writeln!(f, ".")?;
+            } else {
+                writeln!(f, ", missing pages {:?}.", missing_pages)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::scrapers::browser::BrowserOptions;
+    use crate::scrapers::climatico;
+    use crate::scrapers::crawl::CrawlConfig;
+    use crate::scrapers::manifest::ConfigSnapshot;
+
+    /// The name of the page source file of `url`.
+    fn file_name(url: &str) -> String {
+        url_to_html_file_name(&Url::parse(url).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn finds_the_listing_and_page_of_a_file() {
+        let listing_urls = vec![
+            "https://www.climatico.ro/aer_conditionat".to_string(),
+            "https://www.climatico.ro/ac".to_string(),
+            "https://www.climatico.ro/ac/inverter?product_list_limit=36".to_string(),
+        ];
+
+        assert_eq!(
+            listing_and_page(
+                &file_name("https://www.climatico.ro/aer_conditionat?product_list_limit=36"),
+                &listing_urls
+            ),
+            Some(("/aer_conditionat".to_string(), 1))
+        );
+        assert_eq!(
+            listing_and_page(
+                &file_name("https://www.climatico.ro/ac/inverter?p=3&product_list_limit=36"),
+                &listing_urls
+            ),
+            Some(("/ac/inverter".to_string(), 3))
+        );
+        assert_eq!(
+            listing_and_page(&file_name("https://www.climatico.ro/ac?p=2"), &listing_urls),
+            Some(("/ac".to_string(), 2))
+        );
+        assert_eq!(
+            listing_and_page(&file_name("https://www.climatico.ro/other"), &listing_urls),
+            None
+        );
+    }
+
+    #[test]
+    fn counts_the_pages_listed_by_the_last_run() {
+        let output_root = std::env::temp_dir().join(format!("site-stats-{}", std::process::id()));
+        let output_paths = SiteOutputPaths::new(&output_root, climatico::SITE_ID);
+        let listing_url = "https://www.climatico.ro/aer_conditionat";
+        let saved_pages = [
+            file_name(listing_url),
+            file_name(&format!("{}?p=3", listing_url)),
+        ];
+        let config = ConfigSnapshot {
+            crawl: CrawlConfig::default(),
+            browser: BrowserOptions::default(),
+        };
+        let mut manifest = RunManifest::start(climatico::SITE_ID, config, &[listing_url]);
+        manifest.report.files = saved_pages.to_vec();
+        manifest
+            .report
+            .files
+            .push(file_name(&format!("{}?p=2", listing_url)));
+
+        std::fs::create_dir_all(&output_paths.page_sources).unwrap();
+        manifest.write(&output_paths.run_manifest).unwrap();
+
+        for page in saved_pages
+            .iter()
+            .chain(&[file_name("https://www.climatico.ro/old")])
+        {
+            std::fs::write(
+                output_paths.page_sources.join(page),
+                r#"<li class="product-item"></li>"#,
+            )
+            .unwrap();
+        }
+
+        let stats = site_stats(&climatico::scraper_info(), &output_paths).unwrap();
+
+        assert_eq!(stats.page_count, 2);
+        assert_eq!(stats.missing_files, 1);
+        assert_eq!(stats.products_per_page.get(&1), Some(&2));
+        assert_eq!(stats.listings["/aer_conditionat"].pages, vec![1, 3]);
+        assert_eq!(stats.listings["/aer_conditionat"].missing_pages(), vec![2]);
+
+        std::fs::remove_dir_all(&output_root).unwrap();
+    }
+}
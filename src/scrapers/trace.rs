@@ -0,0 +1,232 @@
+//! Recording WebDriver commands to a trace file and replaying them.
+//!
+//! A trace is a JSON Lines file with one [TraceEntry] per command, in the order the commands
+//! were issued. Replaying a trace against a WebDriver server issues the same commands with the
+//! same pacing, which helps reproducing scraping bugs deterministically.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use fantoccini::error::CmdError;
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::scrapers::fetch::FetchClient;
+
+/// A WebDriver command issued by a scraper.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum TracedCommand {
+    /// Navigate to a URL.
+    Goto { url: String },
+    /// Get the current page's source.
+    Source,
+    /// Check whether an element matching a CSS selector is on the page.
+    Find { selector: String },
+    /// Execute a script in the current page.
+    Execute { script: String },
+}
+
+/// What a [TracedCommand] returned.
+#[derive(Debug)]
+pub enum CommandOutput {
+    /// The command doesn't return anything.
+    None,
+    /// The page source returned by [TracedCommand::Source].
+    Source(String),
+    /// Whether [TracedCommand::Find] found a matching element.
+    Found(bool),
+    /// The value returned by the script of [TracedCommand::Execute].
+    Value(Value),
+}
+
+impl TracedCommand {
+    /// Issues this command with `client`.
+    ///
+    /// A [TracedCommand::Find] that doesn't match returns `CommandOutput::Found(false)` instead
+    /// of failing.
+    pub async fn issue<C: FetchClient + ?Sized>(
+        &self,
+        client: &mut C,
+    ) -> Result<CommandOutput, CmdError> {
+        match self {
+            TracedCommand::Goto { url } => {
+                client.goto(url).await?;
+
+                Ok(CommandOutput::None)
+            }
+            TracedCommand::Source => Ok(CommandOutput::Source(client.source().await?)),
+            TracedCommand::Find { selector } => {
+                Ok(CommandOutput::Found(client.has_element(selector).await?))
+            }
+            TracedCommand::Execute { script } => {
+                Ok(CommandOutput::Value(client.execute(script).await?))
+            }
+        }
+    }
+}
+
+/// A command recorded in a trace file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TraceEntry {
+    /// When the command was issued, in milliseconds since the trace started.
+    pub offset_ms: u64,
+    /// How long the command took, in milliseconds.
+    pub duration_ms: u64,
+    /// Whether the command succeeded.
+    pub ok: bool,
+    #[serde(flatten)]
+    pub command: TracedCommand,
+}
+
+/// Writes [TraceEntry]s to a trace file.
+#[derive(Debug)]
+pub struct TraceWriter {
+    writer: BufWriter<File>,
+    started_at: Instant,
+}
+
+impl TraceWriter {
+    /// Creates a new trace file at `path`, replacing any existing one.
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        Ok(Self {
+            writer: BufWriter::new(File::create(path)?),
+            started_at: Instant::now(),
+        })
+    }
+
+    /// Records `command`, which was issued at `issued_at` and succeeded if `ok` is `true`.
+    pub fn record(
+        &mut self,
+        command: &TracedCommand,
+        issued_at: Instant,
+        ok: bool,
+    ) -> std::io::Result<()> {
+        let entry = TraceEntry {
+            offset_ms: issued_at.duration_since(self.started_at).as_millis() as u64,
+            duration_ms: issued_at.elapsed().as_millis() as u64,
+            ok,
+            command: command.clone(),
+        };
+
+        serde_json::to_writer(&mut self.writer, &entry)?;
+        self.writer.write_all(b"\n")?;
+        // Flushed after every command, so the trace is complete even if the scraper crashes.
+        self.writer.flush()
+    }
+}
+
+/// Reads all the entries of the trace file at `path`.
+pub fn read_trace(path: &Path) -> std::io::Result<Vec<TraceEntry>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut entries = Vec::new();
+
+    for line in reader.lines() {
+        let line = line?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        entries.push(serde_json::from_str(&line)?);
+    }
+
+    Ok(entries)
+}
+
+/// Issues the commands of a trace with `client`, keeping the trace's original pacing. With
+/// `slowmo`, every command is delayed by it on top of that, as when scraping.
+///
+/// Commands that failed when they were recorded may fail again without stopping the replay.
+/// Any other failure stops it.
+pub async fn replay<C: FetchClient + ?Sized>(
+    client: &mut C,
+    entries: &[TraceEntry],
+    slowmo: Option<Duration>,
+) -> Result<(), CmdError> {
+    let started_at = Instant::now();
+    // Pauses are added up, since commands are issued at their offset from the start.
+    let mut slowmo_so_far = Duration::from_secs(0);
+
+    for (i, entry) in entries.iter().enumerate() {
+        slowmo_so_far += slowmo.unwrap_or_default();
+
+        let offset = Duration::from_millis(entry.offset_ms) + slowmo_so_far;
+
+        if let Some(wait) = offset.checked_sub(started_at.elapsed()) {
+            tokio::timer::delay_for(wait).await;
+        }
+
+        info!(
+            "Replaying command {}/{}: {:?}",
+            i + 1,
+            entries.len(),
+            entry.command
+        );
+
+        match entry.command.issue(client).await {
+            Ok(_) => {}
+            Err(e) if !entry.ok => info!("Command failed, as it did when recorded: {}", e),
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scrapers::fetch::MockFetchClient;
+
+    fn entry(offset_ms: u64, command: TracedCommand) -> TraceEntry {
+        TraceEntry {
+            offset_ms,
+            duration_ms: 0,
+            ok: true,
+            command,
+        }
+    }
+
+    #[tokio::test]
+    async fn replays_commands_with_their_pacing_and_slowmo() {
+        let entries = vec![
+            entry(
+                0,
+                TracedCommand::Goto {
+                    url: "https://www.climatico.ro/ac".to_string(),
+                },
+            ),
+            entry(20, TracedCommand::Source),
+            entry(
+                30,
+                TracedCommand::Goto {
+                    url: "https://www.climatico.ro/ac?p=2".to_string(),
+                },
+            ),
+        ];
+        let mut client = MockFetchClient::default();
+        let started_at = Instant::now();
+
+        replay(&mut client, &entries, Some(Duration::from_millis(10)))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            client.visited_urls,
+            vec![
+                "https://www.climatico.ro/ac",
+                "https://www.climatico.ro/ac?p=2"
+            ]
+        );
+        // The last command waits for its offset and three pauses.
+        assert!(started_at.elapsed() >= Duration::from_millis(60));
+    }
+}